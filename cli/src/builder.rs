@@ -0,0 +1,500 @@
+//! Building the player database from the NHL APIs.
+//!
+//! Every player in the search index is looked up on their landing page, and added to each team
+//! they played for in the requested seasons.
+//!
+//! A build can take hours, so its progress is checkpointed to a file every few hundred players
+//! and can be resumed from there after a crash.
+//!
+//! Offline, a player whose landing page isn't cached is left out and reported, but not added to
+//! the failures ledger: nothing was requested, so there's nothing for `retry-failed` to retry.
+
+use crate::nhl_api::{NhlApi, PlayerDetails, PlayerSearchResult};
+use crate::failures::{self, Failure};
+use crate::Settings;
+use chrono::{Datelike, NaiveDate};
+use journeyman_core::dataset::{PlayerDatabase, PlayerInfo};
+use journeyman_core::teams;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+// Players between checkpoints
+const CHECKPOINT_EVERY: usize = 500;
+// Regular seasons open in October; until then the latest season is the one that started the year before
+const SEASON_START_MONTH: u32 = 10;
+
+/// Seasons to collect, by starting year, inclusive.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SeasonRange {
+    pub start_year: u32,
+    pub end_year: u32,
+}
+
+impl SeasonRange {
+    /// Season ids as the dataset lists them, e.g. "20232024".
+    pub fn seasons(&self) -> Vec<String> {
+        (self.start_year..=self.end_year)
+            .map(|year| format!("{}{}", year, year + 1))
+            .collect()
+    }
+
+    /// The seasons a database covers.
+    pub fn covered_by(database: &PlayerDatabase) -> Option<SeasonRange> {
+        let start = |season: &String| season.get(..4)?.parse().ok();
+        Some(SeasonRange {
+            start_year: database.seasons_covered.iter().filter_map(start).min()?,
+            end_year: database.seasons_covered.iter().filter_map(start).max()?,
+        })
+    }
+}
+
+/// Starting year of the latest season to have begun by `date`, e.g. 2025 for 2025-26.
+pub fn season_start_year(date: NaiveDate) -> u32 {
+    let year = date.year() as u32;
+    if date.month() >= SEASON_START_MONTH { year } else { year - 1 }
+}
+
+/// Progress of a build: the players to look up, how many have been, and the teams found so far.
+///
+/// This is what's checkpointed. Players are processed strictly in `candidates` order, so
+/// `processed` is all it takes to know where to pick up, and a resumed build ends up with
+/// exactly the state an uninterrupted one would have.
+#[derive(Serialize, Deserialize)]
+pub struct BuildState {
+    pub range: SeasonRange,
+    /// Timestamp for the finished database, fixed when the build starts
+    pub generated_at: String,
+    candidates: Vec<PlayerSearchResult>,
+    processed: usize,
+    players_with_teams: usize,
+    failures: Vec<Failure>,
+    /// Players skipped offline because their landing page isn't cached
+    #[serde(default)]
+    uncached: BTreeSet<String>,
+    players: BTreeMap<String, PlayerInfo>,
+    /// Player ids by current team code
+    teams: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl BuildState {
+    pub fn new(range: SeasonRange, generated_at: String, candidates: Vec<PlayerSearchResult>) -> BuildState {
+        println!("🏒 Processing {} players to build team associations...", candidates.len());
+
+        BuildState {
+            range,
+            generated_at,
+            candidates,
+            processed: 0,
+            players_with_teams: 0,
+            failures: Vec::new(),
+            uncached: BTreeSet::new(),
+            players: BTreeMap::new(),
+            // Initialize current teams in the database
+            teams: teams::TEAMS.iter().map(|team| (team.code.to_string(), BTreeSet::new())).collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<BuildState, Box<dyn std::error::Error>> {
+        let state: BuildState = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| format!("{} is not a build checkpoint: {}", path.display(), e))?;
+        println!("⏯️  Resuming from {}: {}/{} players processed", path.display(), state.processed, state.candidates.len());
+        Ok(state)
+    }
+
+    // Write to a temporary file first so a kill mid-write leaves the previous checkpoint intact
+    fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let partial = path.with_extension("partial");
+        fs::write(&partial, serde_json::to_string(self)?)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    fn apply(&mut self, result: DetailsResult) {
+        let player = &self.candidates[self.processed];
+        self.processed += 1;
+
+        match result {
+            Ok(Some(details)) => {
+                let player_info = details.player_info();
+                println!("   Processing player: {} (ID: {})", player_info.name, player.player_id);
+
+                // Add player to each of their teams
+                let player_teams = details.teams_between(self.range.start_year, self.range.end_year);
+                let mut on_a_team = false;
+                for team_code in &player_teams {
+                    if let Some(team_players) = self.teams.get_mut(team_code) {
+                        team_players.insert(player_info.id.clone());
+                        on_a_team = true;
+                    }
+                }
+                if on_a_team {
+                    self.players_with_teams += 1;
+                    self.players.insert(player_info.id.clone(), player_info);
+                }
+            }
+            Ok(None) => {
+                println!("📭 No cached details for {} (ID: {})", player.name, player.player_id);
+                self.uncached.insert(player.player_id.clone());
+            }
+            Err(e) => {
+                println!("⚠️  Failed to fetch player details for {} (ID: {}): {}", player.name, player.player_id, e);
+                self.failures.push(Failure { id: player.player_id.clone(), name: player.name.clone(), error: e });
+            }
+        }
+    }
+
+    /// Take the players this run looked up off the failures ledger, and add the ones that failed.
+    /// Players that weren't in the cache weren't looked up, so their entries are left alone.
+    pub fn record_failures(&self, ledger: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let attempted = self.candidates[..self.processed].iter()
+            .map(|player| player.player_id.as_str())
+            .filter(|id| !self.uncached.contains(*id));
+        let outstanding = failures::record(ledger, attempted, &self.failures)?;
+        if outstanding > 0 {
+            println!("📝 {} players failed and are missing from the database; see {} and run retry-failed",
+                     outstanding, ledger.display());
+        }
+        Ok(())
+    }
+}
+
+/// Look up every remaining player in `state`, saving it to `checkpoint` as it goes.
+///
+/// Landing pages are fetched by `settings.concurrency` workers sharing the API's rate limiter. Results
+/// are applied in `candidates` order whatever order they arrive in, so the output and the
+/// progress lines are the same at any concurrency.
+pub async fn collect_team_players(
+    api: &NhlApi,
+    settings: &Settings,
+    state: &mut BuildState,
+    checkpoint: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    collect_until(api, settings, state, checkpoint, None).await
+}
+
+// As `collect_team_players`, but give up without a final checkpoint once `stop_after` players
+// have been processed, the way a killed build would
+async fn collect_until(
+    api: &NhlApi,
+    settings: &Settings,
+    state: &mut BuildState,
+    checkpoint: Option<&Path>,
+    stop_after: Option<usize>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let range = state.range;
+    println!("📅 Including seasons {}-{} to {}-{}", range.start_year, range.start_year + 1, range.end_year, range.end_year + 1);
+    if api.is_offline() {
+        println!("📂 Offline: reading landing pages from the cache");
+    } else {
+        println!("⚙️  {} concurrent requests, one every {}ms", settings.concurrency, settings.delay);
+    }
+
+    let first = state.processed;
+    let ids: Arc<Vec<String>> = Arc::new(state.candidates[first..].iter().map(|player| player.player_id.clone()).collect());
+    let mut results = fetch_all_details(api, settings, ids);
+
+    let total_players = state.candidates.len();
+    // Results that arrived ahead of an earlier player's
+    let mut pending: BTreeMap<usize, DetailsResult> = BTreeMap::new();
+
+    while let Some((index, result)) = results.recv().await {
+        pending.insert(first + index, result);
+        while let Some(result) = pending.remove(&state.processed) {
+            state.apply(result);
+
+            // Progress indicator every 100 players
+            if state.processed.is_multiple_of(100) {
+                println!("📊 Progress: {}/{} players processed ({:.1}%), {} with teams, {} errors",
+                         state.processed, total_players,
+                         (state.processed as f64 / total_players as f64) * 100.0,
+                         state.players_with_teams, state.failures.len());
+            }
+            if let Some(path) = checkpoint.filter(|_| state.processed.is_multiple_of(CHECKPOINT_EVERY)) {
+                state.save(path)?;
+                println!("💾 Checkpoint saved to {}", path.display());
+            }
+            if stop_after == Some(state.processed) {
+                return Ok(());
+            }
+        }
+    }
+
+    println!("✅ Completed processing {} players", state.processed);
+    println!("   Players with team data: {}", state.players_with_teams);
+    println!("   API errors: {}", state.failures.len());
+    if !state.uncached.is_empty() {
+        println!("   Not in the cache: {} (run without --offline to fetch them)", state.uncached.len());
+    }
+    if let Some(path) = checkpoint {
+        state.save(path)?;
+    }
+    Ok(())
+}
+
+// None when offline and the player isn't cached
+type DetailsResult = Result<Option<PlayerDetails>, String>;
+
+// Start the workers; each result is sent with the index of its id in `ids`
+fn fetch_all_details(api: &NhlApi, settings: &Settings, ids: Arc<Vec<String>>) -> mpsc::Receiver<(usize, DetailsResult)> {
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel(settings.concurrency as usize * 2);
+
+    for _ in 0..settings.concurrency {
+        let api = api.clone();
+        let next = next.clone();
+        let ids = ids.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(id) = ids.get(index) else { break };
+                let result = api.fetch_player_details(id).await.map_err(|e| e.to_string());
+                if sender.send((index, result)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    receiver
+}
+
+/// Player ids added to each team, in the order they were added.
+pub type Additions = BTreeMap<String, Vec<String>>;
+
+/// Add the players a build found to a database, each team's new players in name order.
+/// Returns the memberships the database didn't already have.
+pub fn merge_into(database: &mut PlayerDatabase, state: &BuildState) -> Additions {
+    let mut additions = Additions::new();
+    for (team, ids) in &state.teams {
+        let mut player_list: Vec<&PlayerInfo> = ids.iter().map(|id| &state.players[id]).collect();
+        player_list.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        database.teams.entry(team.clone()).or_default();
+        for player in player_list {
+            if !database.teams_for_player(&player.id).contains(team) {
+                additions.entry(team.clone()).or_default().push(player.id.clone());
+            }
+            database.insert(team, player.clone());
+        }
+    }
+    additions
+}
+
+pub fn print_summary(database: &PlayerDatabase) {
+    let roster_spots: usize = database.teams.values().map(|ids| ids.len()).sum();
+
+    println!("\n📊 Database Summary:");
+    println!("   Teams: {}", database.teams.len());
+    println!("   Unique players: {}", database.players.len());
+    println!("   Team memberships: {}", roster_spots);
+    if let (Some(first), Some(last)) = (database.seasons_covered.first(), database.seasons_covered.last()) {
+        println!("   Seasons covered: {} to {}", first, last);
+    }
+}
+
+/// List the memberships an update added, and which of the players are new to the database.
+pub fn print_additions(database: &PlayerDatabase, additions: &Additions, new_players: &BTreeSet<String>, since: &str) {
+    let added: usize = additions.values().map(|ids| ids.len()).sum();
+    println!("\n📋 Changes since the build of {}:", since);
+    println!("   {} team memberships added, {} new players", added, new_players.len());
+    for (team, ids) in additions {
+        let players: Vec<String> = ids.iter()
+            .map(|id| {
+                let name = database.player(id).map(|p| p.name.as_str()).unwrap_or("?");
+                let marker = if new_players.contains(id) { " 🆕" } else { "" };
+                format!("{} ({}){}", name, id, marker)
+            })
+            .collect();
+        println!("   {} +{}: {}", team, ids.len(), players.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::ResponseCache;
+    use std::path::PathBuf;
+
+    const PLAYERS: usize = 1200;
+    const TEAM_NAMES: [&str; 4] = ["Boston Bruins", "Montréal Canadiens", "Toronto Maple Leafs", "Atlanta Thrashers"];
+
+    // Names sort in a different order from ids
+    fn last_name(index: usize) -> String {
+        format!("Player{:04}", index * 7919 % PLAYERS)
+    }
+
+    fn search_results() -> Vec<PlayerSearchResult> {
+        (0..PLAYERS)
+            .map(|index| PlayerSearchResult {
+                player_id: (8470000 + index).to_string(),
+                name: format!("Test {}", last_name(index)),
+                last_season_id: None,
+                active: false,
+            })
+            .collect()
+    }
+
+    // A cache with a landing page for every player, so builds can run offline
+    async fn fill_cache(name: &str) -> PathBuf {
+        let cache_dir = std::env::temp_dir().join(format!("nhl-player-db-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&cache_dir);
+        let cache = ResponseCache::new(&cache_dir);
+        for index in 0..PLAYERS {
+            let seasons: Vec<serde_json::Value> = (0..3)
+                .map(|offset| serde_json::json!({
+                    "season": (2009 + index % 5 + offset) * 10001 + 1,
+                    "teamName": {"default": TEAM_NAMES[(index + offset) % TEAM_NAMES.len()]}
+                }))
+                .collect();
+            let landing = serde_json::json!({
+                "playerId": 8470000 + index,
+                "firstName": {"default": "Test"},
+                "lastName": {"default": last_name(index)},
+                "birthDate": "1990-01-01",
+                "birthCity": {"default": "Kladno"},
+                "birthCountry": "CZE",
+                "position": "C",
+                "seasonTotals": seasons
+            });
+            cache.put(&format!("landing-{}", 8470000 + index), "https://example.com", &landing.to_string()).await.unwrap();
+        }
+        cache_dir
+    }
+
+    fn offline(cache_dir: &Path, concurrency: u32) -> Settings {
+        Settings { delay: 0, cache_dir: cache_dir.to_path_buf(), concurrency, offline: true }
+    }
+
+    fn range() -> SeasonRange {
+        SeasonRange { start_year: 2010, end_year: 2015 }
+    }
+
+    // The database a finished build writes
+    fn output(state: &BuildState) -> String {
+        let mut database = PlayerDatabase::new(state.generated_at.clone(), state.range.seasons());
+        merge_into(&mut database, state);
+        serde_json::to_string_pretty(&database).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resumed_build_matches_an_uninterrupted_one() {
+        let cache_dir = fill_cache("resume").await;
+        let settings = offline(&cache_dir, 4);
+        let api = NhlApi::new(&settings).unwrap();
+        let generated_at = "2025-10-01T00:00:00Z".to_string();
+
+        let mut uninterrupted = BuildState::new(range(), generated_at.clone(), search_results());
+        collect_team_players(&api, &settings, &mut uninterrupted, None).await.unwrap();
+        assert!(uninterrupted.failures.is_empty() && uninterrupted.players_with_teams > 0);
+        let expected = output(&uninterrupted);
+
+        // Stop a build partway between its first and second checkpoints; the players it
+        // processed after the first are lost with it, as they would be in a crash
+        let checkpoint = cache_dir.join("build.checkpoint");
+        let mut interrupted = BuildState::new(range(), generated_at, search_results());
+        collect_until(&api, &settings, &mut interrupted, Some(&checkpoint), Some(CHECKPOINT_EVERY + 200)).await.unwrap();
+        assert_eq!(interrupted.processed, CHECKPOINT_EVERY + 200);
+
+        let mut resumed = BuildState::load(&checkpoint).unwrap();
+        assert_eq!(resumed.processed, CHECKPOINT_EVERY);
+        collect_team_players(&api, &settings, &mut resumed, Some(&checkpoint)).await.unwrap();
+        assert_eq!(output(&resumed), expected);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn output_is_the_same_at_any_concurrency() {
+        let cache_dir = fill_cache("concurrency").await;
+        let generated_at = "2025-10-01T00:00:00Z".to_string();
+
+        let mut outputs = Vec::new();
+        for concurrency in [1, 3, 16] {
+            let settings = offline(&cache_dir, concurrency);
+            let mut state = BuildState::new(range(), generated_at.clone(), search_results());
+            collect_team_players(&NhlApi::new(&settings).unwrap(), &settings, &mut state, None).await.unwrap();
+            assert!(state.processed == PLAYERS && state.failures.is_empty());
+            outputs.push(output(&state));
+        }
+        assert!(outputs.iter().all(|output| *output == outputs[0]));
+
+        // Each roster lists its players by name
+        let database = PlayerDatabase::from_json(&outputs[0]).unwrap();
+        for ids in database.teams.values() {
+            let names: Vec<&str> = ids.iter().map(|id| database.player(id).unwrap().name.as_str()).collect();
+            assert!(names.is_sorted());
+        }
+        assert!(database.teams.values().any(|ids| ids.len() > 1));
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn uncached_players_stay_off_the_failures_ledger() {
+        let cache_dir = fill_cache("uncached").await;
+        for id in ["8470003", "8470700"] {
+            fs::remove_file(cache_dir.join("refs").join(format!("landing-{}.json", id))).unwrap();
+        }
+        // 8470700 failed online before; being missing from the cache doesn't make it a success
+        let ledger = cache_dir.join("failed_players.csv");
+        fs::write(&ledger, "ID,Name,Error\n8470700,Test Player,HTTP 500\n8470010,Test Player,HTTP 500\n").unwrap();
+
+        let settings = offline(&cache_dir, 4);
+        let mut state = BuildState::new(range(), "2025-10-01T00:00:00Z".to_string(), search_results());
+        collect_team_players(&NhlApi::new(&settings).unwrap(), &settings, &mut state, None).await.unwrap();
+        assert!(state.failures.is_empty());
+        assert_eq!(state.uncached, BTreeSet::from(["8470003".to_string(), "8470700".to_string()]));
+
+        state.record_failures(&ledger).unwrap();
+        let ids: Vec<String> = failures::load(&ledger).unwrap().into_iter().map(|failure| failure.id).collect();
+        assert_eq!(ids, vec!["8470700"]);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn seasons_start_in_october() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        assert_eq!(season_start_year(date(2025, 9, 30)), 2024);
+        assert_eq!(season_start_year(date(2025, 10, 1)), 2025);
+        assert_eq!(season_start_year(date(2026, 6, 20)), 2025);
+        assert_eq!(season_start_year(date(2026, 12, 31)), 2026);
+    }
+
+    #[tokio::test]
+    async fn build_looks_up_players_the_index_lists_as_retired() {
+        // The search index's last season can lag behind the landing page, so a build trusts only the latter
+        let cache_dir = std::env::temp_dir().join(format!("nhl-player-db-retired-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_dir);
+        let landing = serde_json::json!({
+            "playerId": 8470000,
+            "firstName": {"default": "Test"},
+            "lastName": {"default": "Player"},
+            "birthDate": "1975-01-01",
+            "birthCity": {"default": "Kladno"},
+            "birthCountry": "CZE",
+            "position": "C",
+            "seasonTotals": [{"season": 20122013, "teamName": {"default": "Boston Bruins"}}]
+        });
+        ResponseCache::new(&cache_dir).put("landing-8470000", "https://example.com", &landing.to_string()).await.unwrap();
+        let settings = offline(&cache_dir, 1);
+        let api = NhlApi::new(&settings).unwrap();
+
+        let player = PlayerSearchResult {
+            player_id: "8470000".to_string(),
+            name: "Test Player".to_string(),
+            last_season_id: Some("19981999".to_string()),
+            active: false,
+        };
+        let mut state = BuildState::new(range(), String::new(), vec![player]);
+        collect_team_players(&api, &settings, &mut state, None).await.unwrap();
+        assert_eq!(state.players_with_teams, 1);
+        assert!(state.teams["BOS"].contains("8470000"));
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+//! Raw NHL API responses, kept so a database can be rebuilt without refetching anything.
+//!
+//! Bodies are stored once each under their SHA-256 (`objects/ab/abcd...`), and every request
+//! has a small ref (`refs/{key}.json`) with its URL, the hash of the body it last returned and
+//! when that was fetched. Refetching a page that hasn't changed only rewrites its ref.
+//!
+//! Reads and writes go through `tokio::fs`, so the fetch workers never block the runtime on disk.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+#[derive(Serialize, Deserialize)]
+pub struct CacheRef {
+    pub url: String,
+    pub sha256: String,
+    /// RFC 3339
+    pub fetched_at: String,
+}
+
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(dir: &Path) -> ResponseCache {
+        ResponseCache { dir: dir.to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn ref_path(&self, key: &str) -> PathBuf {
+        self.dir.join("refs").join(format!("{}.json", key))
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join("objects").join(&sha256[..2]).join(sha256)
+    }
+
+    /// The last response stored for `key`, and its ref.
+    pub async fn get(&self, key: &str) -> Option<(CacheRef, String)> {
+        let cache_ref: CacheRef = serde_json::from_str(&fs::read_to_string(self.ref_path(key)).await.ok()?).ok()?;
+        let body = fs::read_to_string(self.object_path(&cache_ref.sha256)).await.ok()?;
+        Some((cache_ref, body))
+    }
+
+    /// Store `body` as the latest response for `key`.
+    pub async fn put(&self, key: &str, url: &str, body: &str) -> std::io::Result<()> {
+        let sha256 = format!("{:x}", Sha256::digest(body.as_bytes()));
+        let object = self.object_path(&sha256);
+        if !fs::try_exists(&object).await? {
+            write_atomically(&object, body).await?;
+        }
+
+        let cache_ref = CacheRef {
+            url: url.to_string(),
+            sha256,
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+        };
+        write_atomically(&self.ref_path(key), &serde_json::to_string(&cache_ref)?).await
+    }
+}
+
+// Workers write concurrently and runs get killed; never leave a half-written file under a real name
+async fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let partial = path.with_extension(format!("partial-{}", std::process::id()));
+    fs::write(&partial, contents).await?;
+    fs::rename(&partial, path).await
+}
@@ -0,0 +1,99 @@
+//! The failures ledger: players whose landing page couldn't be fetched, and so are missing from
+//! the database, as a CSV of `ID,Name,Error`.
+//!
+//! Every build, update and `retry-failed` run rewrites it: players it looked up are taken off,
+//! and the ones that failed again are put back with their latest error. Ledgers with only the
+//! `ID,Name` columns, like the hand-made `failed_players.csv`, are read too.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+const HEADER: &str = "ID,Name,Error";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Failure {
+    pub id: String,
+    pub name: String,
+    pub error: String,
+}
+
+// Quote a field if it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"").replace('\n', " "))
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// The ledger at `path`; empty if there isn't one.
+pub fn load(path: &Path) -> Result<Vec<Failure>, Box<dyn std::error::Error>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut failures = Vec::new();
+    for (number, line) in contents.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = parse_line(line).into_iter();
+        let id = fields.next().unwrap_or_default();
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("{} line {}: '{}' is not a player id", path.display(), number + 1, id).into());
+        }
+        failures.push(Failure {
+            id,
+            name: fields.next().unwrap_or_default(),
+            error: fields.next().unwrap_or_default(),
+        });
+    }
+    Ok(failures)
+}
+
+/// Update the ledger after a run that looked up `attempted` and failed on `failed`.
+/// Returns how many players it now lists.
+pub fn record<'a>(path: &Path, attempted: impl Iterator<Item = &'a str>, failed: &[Failure]) -> Result<usize, Box<dyn std::error::Error>> {
+    let attempted: HashSet<&str> = attempted.collect();
+    let mut failures: Vec<Failure> = load(path)?.into_iter().filter(|failure| !attempted.contains(failure.id.as_str())).collect();
+    failures.extend(failed.iter().cloned());
+    failures.sort_by(|a, b| a.id.cmp(&b.id));
+
+    if failures.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(0);
+    }
+
+    let mut contents = format!("{}\n", HEADER);
+    for failure in &failures {
+        contents.push_str(&format!("{},{},{}\n", csv_field(&failure.id), csv_field(&failure.name), csv_field(&failure.error)));
+    }
+    fs::write(path, contents)?;
+    Ok(failures.len())
+}
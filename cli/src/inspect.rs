@@ -0,0 +1,232 @@
+//! Read-only commands for looking at a built database: `validate`, `diff`, `stats` and `query`.
+
+use journeyman_core::dataset::{PlayerDatabase, PlayerInfo};
+use journeyman_core::teams;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+
+fn load(path: &str) -> Result<PlayerDatabase, Box<dyn std::error::Error>> {
+    Ok(PlayerDatabase::from_json(&fs::read_to_string(path)?)?)
+}
+
+fn describe(player: &PlayerInfo) -> String {
+    format!("{} ({})", player.name, player.id)
+}
+
+// Everything wrong with a database that would break the game or the edge service
+fn problems(database: &PlayerDatabase) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for team in teams::TEAMS.iter() {
+        if !database.teams.contains_key(team.code) {
+            problems.push(format!("{} ({}) has no roster", team.code, team.name));
+        }
+    }
+
+    let mut codes: Vec<&String> = database.teams.keys().collect();
+    codes.sort();
+    let mut on_a_team = HashSet::new();
+    for code in codes {
+        let ids = &database.teams[code];
+        if teams::by_code(code).is_none() {
+            problems.push(format!("{} is not a current team code", code));
+        }
+        if ids.is_empty() {
+            problems.push(format!("{} has no players", code));
+        }
+        let mut seen = HashSet::new();
+        for id in ids {
+            if !seen.insert(id) {
+                problems.push(format!("{} lists player {} more than once", code, id));
+            }
+            if !database.players.contains_key(id) {
+                problems.push(format!("{} lists player {}, who isn't in the players table", code, id));
+            }
+            on_a_team.insert(id);
+        }
+    }
+
+    let mut ids: Vec<&String> = database.players.keys().collect();
+    ids.sort();
+    for id in ids {
+        let player = &database.players[id];
+        if &player.id != id {
+            problems.push(format!("player {} is stored under id {}", player.id, id));
+        }
+        if player.name.trim().is_empty() {
+            problems.push(format!("player {} has no name", id));
+        }
+        if !on_a_team.contains(id) {
+            problems.push(format!("{} isn't on any team", describe(player)));
+        }
+    }
+
+    if database.seasons_covered.is_empty() {
+        problems.push("no seasons are listed as covered".to_string());
+    }
+    problems
+}
+
+pub fn validate(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load(path)?;
+    let problems = problems(&database);
+    if problems.is_empty() {
+        println!("✅ {} is valid: {} teams, {} players", path, database.teams.len(), database.players.len());
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("❌ {}", problem);
+    }
+    Err(format!("{} has {} problems", path, problems.len()).into())
+}
+
+// Up to `limit` names from a list of ids, with a count of the rest
+fn summarize(database: &PlayerDatabase, ids: &[&String], limit: usize) -> String {
+    let mut names: Vec<String> = ids.iter()
+        .take(limit)
+        .map(|id| database.player(id).map(describe).unwrap_or_else(|| id.to_string()))
+        .collect();
+    if ids.len() > limit {
+        names.push(format!("and {} more", ids.len() - limit));
+    }
+    names.join(", ")
+}
+
+pub fn diff(old_path: &str, new_path: &str, limit: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let old = load(old_path)?;
+    let new = load(new_path)?;
+
+    let old_ids: BTreeSet<&String> = old.players.keys().collect();
+    let new_ids: BTreeSet<&String> = new.players.keys().collect();
+    let added: Vec<&String> = new_ids.difference(&old_ids).copied().collect();
+    let removed: Vec<&String> = old_ids.difference(&new_ids).copied().collect();
+
+    println!("📊 {} → {}", old_path, new_path);
+    println!("   Players: {} → {} (+{}, -{})", old.players.len(), new.players.len(), added.len(), removed.len());
+    if !added.is_empty() {
+        println!("   Added: {}", summarize(&new, &added, limit));
+    }
+    if !removed.is_empty() {
+        println!("   Removed: {}", summarize(&old, &removed, limit));
+    }
+
+    let codes: BTreeSet<&String> = old.teams.keys().chain(new.teams.keys()).collect();
+    let mut unchanged = 0;
+    for code in codes {
+        let before: BTreeSet<&String> = old.teams.get(code).into_iter().flatten().collect();
+        let after: BTreeSet<&String> = new.teams.get(code).into_iter().flatten().collect();
+        let joined: Vec<&String> = after.difference(&before).copied().collect();
+        let left: Vec<&String> = before.difference(&after).copied().collect();
+        if joined.is_empty() && left.is_empty() {
+            unchanged += 1;
+            continue;
+        }
+
+        println!("\n🏒 {}: {} → {} players", code, before.len(), after.len());
+        if !joined.is_empty() {
+            println!("   + {}", summarize(&new, &joined, limit));
+        }
+        if !left.is_empty() {
+            println!("   - {}", summarize(&old, &left, limit));
+        }
+    }
+    println!("\n   {} teams unchanged", unchanged);
+    Ok(())
+}
+
+pub fn stats(path: &str, top: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load(path)?;
+    let roster_spots: usize = database.teams.values().map(|ids| ids.len()).sum();
+
+    println!("📊 {}", path);
+    println!("   Schema version: {}", database.schema_version);
+    println!("   Generated: {}", database.generated_at);
+    if let (Some(first), Some(last)) = (database.seasons_covered.first(), database.seasons_covered.last()) {
+        println!("   Seasons: {} to {} ({})", first, last, database.seasons_covered.len());
+    }
+    println!("   Teams: {}", database.teams.len());
+    println!("   Unique players: {}", database.players.len());
+    println!("   Team memberships: {}", roster_spots);
+
+    let mut rosters: Vec<(&String, usize)> = database.teams.iter().map(|(code, ids)| (code, ids.len())).collect();
+    rosters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    println!("\n🏒 Players per team:");
+    for (code, count) in rosters {
+        println!("   {} {:>5}", code, count);
+    }
+
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for player in database.players.values() {
+        *positions.entry(player.position.as_deref().unwrap_or("?")).or_default() += 1;
+    }
+    let mut positions: Vec<(&str, usize)> = positions.into_iter().collect();
+    positions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    println!("\n🧤 Positions:");
+    for (position, count) in positions {
+        println!("   {:<2} {:>5}", position, count);
+    }
+
+    let mut journeymen: Vec<&PlayerInfo> = database.players.values().collect();
+    journeymen.sort_by(|a, b| {
+        database.teams_for_player(&b.id).len().cmp(&database.teams_for_player(&a.id).len())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    println!("\n🧳 Most teams:");
+    for player in journeymen.into_iter().take(top) {
+        let mut codes = database.teams_for_player(&player.id).to_vec();
+        codes.sort();
+        println!("   {} - {} teams: {}", describe(player), codes.len(), codes.join(", "));
+    }
+    Ok(())
+}
+
+pub fn query(path: &str, player: Option<&str>, team_codes: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let database = load(path)?;
+
+    if let Some(query) = player {
+        // An exact id, otherwise every name containing the query
+        let query_lower = query.to_lowercase();
+        let mut matches: Vec<&PlayerInfo> = match database.player(query) {
+            Some(player) => vec![player],
+            None => database.players.values().filter(|p| p.name.to_lowercase().contains(&query_lower)).collect(),
+        };
+        matches.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        if matches.is_empty() {
+            return Err(format!("No player matches '{}'", query).into());
+        }
+        for p in matches {
+            let mut codes = database.teams_for_player(&p.id).to_vec();
+            codes.sort();
+            println!("{}", describe(p));
+            println!("   Position: {}", p.position.as_deref().unwrap_or("?"));
+            println!("   Born: {} in {}", p.birth_date.as_deref().unwrap_or("?"), p.birth_place.as_deref().unwrap_or("?"));
+            println!("   Teams: {}", codes.join(", "));
+        }
+        return Ok(());
+    }
+
+    if team_codes.is_empty() {
+        return Err("Give a --player or one or more --teams".into());
+    }
+    // Players who played for every listed team
+    let codes: Vec<String> = team_codes.iter().map(|code| code.trim().to_uppercase()).collect();
+    for code in &codes {
+        if !database.teams.contains_key(code) {
+            return Err(format!("Team {} is not in {}", code, path).into());
+        }
+    }
+    let mut matches: Vec<&PlayerInfo> = database.team_players(&codes[0])
+        .filter(|p| {
+            let played_for = database.teams_for_player(&p.id);
+            codes.iter().all(|code| played_for.contains(code))
+        })
+        .collect();
+    matches.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+    println!("🏒 {} players played for {}", matches.len(), codes.join(", "));
+    for p in matches {
+        println!("   {}", describe(p));
+    }
+    Ok(())
+}
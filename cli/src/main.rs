@@ -1,722 +1,520 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::time::Duration;
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use clap::{Parser, Subcommand};
+use journeyman_core::dataset::{PlayerDatabase, PlayerInfo};
+use journeyman_core::registry::DatasetVersion;
+use journeyman_core::solver;
 use serde::{Deserialize, Serialize};
-use tokio::time::sleep;
+
+mod builder;
+mod cache;
+mod failures;
+mod inspect;
+mod nhl_api;
+mod publish;
+mod rate_limit;
+
+use builder::{BuildState, SeasonRange};
+use nhl_api::{NhlApi, PlayerSearchResult};
 
 #[derive(Parser)]
 #[command(name = "nhl-player-db")]
 #[command(about = "Generate NHL player database from NHL API")]
 struct Cli {
-    /// Output file path for the JSON database
-    #[arg(short, long, default_value = "nhl_players.json")]
-    output: String,
-    
     /// Rate limit delay between requests in milliseconds
-    #[arg(short, long, default_value = "100")]
+    #[arg(short, long, global = true, default_value = "100")]
     delay: u64,
-    
-    /// Start year for season data collection (legacy mode)
-    #[arg(long, default_value = "2015")]
-    start_year: u32,
-    
-    /// End year for season data collection (legacy mode)
-    #[arg(long, default_value = "2025")]
-    end_year: u32,
-    
-    /// Include game-by-game data to find missing players (legacy mode)
-    #[arg(long, default_value = "false")]
-    include_games: bool,
-    
-    /// Use new player search API instead of legacy team/season iteration
-    #[arg(long, default_value = "true")]
-    use_player_search: bool,
-}
 
-#[derive(Deserialize)]
-struct PlayerName {
-    #[serde(rename = "firstName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    first_name: String,
-    #[serde(rename = "lastName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    last_name: String,
-}
+    /// Directory for cached API responses
+    #[arg(long, global = true, default_value = ".nhl-cache")]
+    cache_dir: PathBuf,
 
-// Custom deserializer to extract the "default" field
-fn deserialize_name_field<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let name_field = NameField::deserialize(deserializer)?;
-    Ok(name_field.default)
-}
+    /// Rebuild purely from cached responses, without any requests
+    #[arg(long, global = true)]
+    offline: bool,
 
-// Custom deserializer for optional name fields
-fn deserialize_optional_name_field<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let opt_name_field = Option::<NameField>::deserialize(deserializer)?;
-    Ok(opt_name_field.map(|field| field.default))
-}
+    /// Number of requests to have in flight at once; `--delay` still limits the overall rate
+    #[arg(long, global = true, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
 
-#[derive(Deserialize)]
-struct NameField {
-    #[serde(rename = "default")]
-    default: String,
+    #[command(subcommand)]
+    command: Command,
 }
 
-#[derive(Deserialize)]
-struct RosterData {
-    forwards: Option<Vec<PlayerName>>,
-    defensemen: Option<Vec<PlayerName>>,
-    goalies: Option<Vec<PlayerName>>,
+/// Settings shared by every command that talks to the NHL APIs.
+pub struct Settings {
+    pub delay: u64,
+    pub cache_dir: PathBuf,
+    pub concurrency: u32,
+    pub offline: bool,
 }
 
-// Game data structures for extracting players from game logs
-#[derive(Deserialize)]
-struct ScheduleResponse {
-    games: Vec<GameInfo>,
+#[derive(Subcommand)]
+enum Command {
+    /// Build a new database from every player in the NHL player search
+    Build {
+        /// Output file path for the JSON database
+        #[arg(short, long, default_value = "nhl_players.json")]
+        output: String,
+
+        /// Start year for season data collection
+        #[arg(long, default_value = "2015", conflicts_with = "resume")]
+        start_year: u32,
+
+        /// End year for season data collection
+        #[arg(long, default_value = "2025", conflicts_with = "resume")]
+        end_year: u32,
+
+        /// Continue an interrupted build from its checkpoint (`<output>.checkpoint`)
+        #[arg(long)]
+        resume: bool,
+
+        /// Ledger of players whose details couldn't be fetched
+        #[arg(long, default_value = "failed_players.csv")]
+        failures: PathBuf,
+    },
+
+    /// Refetch active and recently active players and add their new teams to an existing database
+    Update {
+        /// Existing JSON database to update
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Where to write the updated database (default: over the existing one)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Start year of the seasons to refetch (default: the end year)
+        #[arg(long)]
+        start_year: Option<u32>,
+
+        /// End year of the seasons to refetch (default: the latest season to have started)
+        #[arg(long)]
+        end_year: Option<u32>,
+
+        /// Ledger of players whose details couldn't be fetched
+        #[arg(long, default_value = "failed_players.csv")]
+        failures: PathBuf,
+
+        /// Also write the players added to each team to this JSON file
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+
+    /// Fetch the players in the failures ledger again and add them to a database
+    RetryFailed {
+        /// Existing JSON database to add them to
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Where to write the updated database (default: over the existing one)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Ledger of players whose details couldn't be fetched
+        #[arg(long, default_value = "failed_players.csv")]
+        failures: PathBuf,
+    },
+
+    /// Check a database for missing teams, dangling ids and other problems
+    Validate {
+        /// JSON database to check
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+    },
+
+    /// Show the players and team memberships that differ between two databases
+    Diff {
+        /// The older database
+        old: String,
+
+        /// The newer database
+        new: String,
+
+        /// Most players to list per change
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Summarize a database: counts per team, positions and the most-travelled players
+    Stats {
+        /// JSON database to summarize
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Number of most-travelled players to list
+        #[arg(long, default_value = "10")]
+        top: usize,
+    },
+
+    /// Look up a player's teams, or the players who played for every given team
+    Query {
+        /// JSON database to query
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Player id, or part of a name
+        #[arg(long, conflicts_with = "teams")]
+        player: Option<String>,
+
+        /// Team codes that every listed player played for, e.g. BOS,TOR
+        #[arg(long, value_delimiter = ',')]
+        teams: Vec<String>,
+    },
+
+    /// Find the fewest players who together played for every given team
+    Solve {
+        /// Existing JSON database to solve against
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Team codes to cover, e.g. BOS,TOR,MTL
+        #[arg(required = true, value_delimiter = ',')]
+        teams: Vec<String>,
+
+        /// Number of optimal covers to print
+        #[arg(long, default_value = "5")]
+        samples: usize,
+    },
+
+    /// Upload hand-picked daily puzzles from a TOML or JSON calendar
+    UploadCalendar {
+        /// Calendar file (.toml or .json) with a `puzzles` list of date, teams and optional title
+        file: String,
+
+        /// Journeyman service to upload to
+        #[arg(long, default_value = "https://journeyman.edgecompute.app")]
+        endpoint: String,
+
+        /// Admin token for the service
+        #[arg(long, env = "JOURNEYMAN_ADMIN_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+
+    /// Upload a database as a new dataset version and register it with the service
+    Publish {
+        /// Existing JSON database to publish
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Version number to publish as, which must not be registered yet (default: one more than the highest registered)
+        #[arg(long)]
+        version: Option<u32>,
+
+        /// Make the version current once it's registered
+        #[arg(long)]
+        promote: bool,
+
+        /// Write a local KV seed next to this fastly.toml for Viceroy instead of uploading
+        #[arg(long, value_name = "FASTLY_TOML")]
+        local: Option<String>,
+
+        /// Replace the version if the local seed already has it
+        #[arg(long, requires = "local")]
+        force: bool,
+
+        /// ID of the `journeyman` KV store
+        #[arg(long, env = "FASTLY_KV_STORE_ID")]
+        store_id: Option<String>,
+
+        /// Fastly API token with write access to the KV store
+        #[arg(long, env = "FASTLY_API_TOKEN", hide_env_values = true)]
+        api_token: Option<String>,
+
+        /// Journeyman service to register the version with
+        #[arg(long, default_value = "https://journeyman.edgecompute.app")]
+        endpoint: String,
+
+        /// Admin token for the service
+        #[arg(long, env = "JOURNEYMAN_ADMIN_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+    },
+
+    /// Write a database as a dataset version's KV entries, one file per key
+    SplitDataset {
+        /// Existing JSON database to split
+        #[arg(long, default_value = "nhl_players.json")]
+        database: String,
+
+        /// Dataset version number to write the keys under
+        #[arg(long)]
+        version: u32,
+
+        /// Directory to write the entries to
+        #[arg(long, default_value = "kv_entries")]
+        out_dir: String,
+    },
 }
 
-#[derive(Deserialize)]
-struct GameInfo {
-    id: u64,
-    #[serde(rename = "awayTeam")]
-    away_team: TeamGameInfo,
-    #[serde(rename = "homeTeam")]
-    home_team: TeamGameInfo,
+#[derive(Serialize, Deserialize)]
+struct PuzzleCalendar {
+    puzzles: Vec<CalendarEntry>,
 }
 
-#[derive(Deserialize)]
-struct TeamGameInfo {
-    abbrev: String,
+#[derive(Serialize, Deserialize)]
+struct CalendarEntry {
+    /// YYYY-MM-DD
+    date: String,
+    /// Team codes or full names
+    teams: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct GameDetails {
-    #[serde(rename = "awayTeam")]
-    away_team: Option<TeamDetails>,
-    #[serde(rename = "homeTeam")]
-    home_team: Option<TeamDetails>,
+fn write_database(database: &PlayerDatabase, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, serde_json::to_string_pretty(database)?)?;
+    println!("✅ Database saved to: {}", path);
+    println!("📈 File size: {:.2} KB", fs::metadata(path)?.len() as f64 / 1024.0);
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct TeamDetails {
-    #[serde(rename = "skaters")]
-    skaters: Option<Vec<GamePlayer>>,
-    #[serde(rename = "goalies")]
-    goalies: Option<Vec<GamePlayer>>,
+// Where a build into `output` keeps its progress
+fn checkpoint_path(output: &str) -> PathBuf {
+    PathBuf::from(format!("{}.checkpoint", output))
 }
 
-#[derive(Deserialize)]
-struct GamePlayer {
-    #[serde(rename = "firstName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    first_name: String,
-    #[serde(rename = "lastName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    last_name: String,
+async fn build(settings: &Settings, output: &str, range: SeasonRange, resume: bool, ledger: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    println!("🏒 NHL Player Database Generator");
+    println!("Output file: {}", output);
+    println!("Rate limit delay: {}ms", settings.delay);
+
+    let api = NhlApi::new(settings)?;
+    let checkpoint = checkpoint_path(output);
+    let mut state = if resume {
+        // The checkpoint has the seasons and player list the build started with
+        BuildState::load(&checkpoint)?
+    } else {
+        if checkpoint.exists() {
+            return Err(format!("{} has progress from an unfinished build; pass --resume to continue it, or delete it to start over",
+                               checkpoint.display()).into());
+        }
+        let players = api.fetch_all_players().await?;
+        BuildState::new(range, chrono::Utc::now().to_rfc3339(), players)
+    };
+    builder::collect_team_players(&api, settings, &mut state, Some(&checkpoint)).await?;
+
+    // Players are stored once and listed by id under each team, alphabetically by name
+    let mut database = PlayerDatabase::new(state.generated_at.clone(), state.range.seasons());
+    builder::merge_into(&mut database, &state);
+    builder::print_summary(&database);
+    write_database(&database, output)?;
+    state.record_failures(ledger)?;
+    fs::remove_file(&checkpoint)?;
+    Ok(())
 }
 
-// New data structures for player search API
-#[derive(Deserialize)]
-struct PlayerSearchResult {
-    #[serde(rename = "playerId")]
-    player_id: String,
-    name: String,
-    #[serde(rename = "positionCode")]
-    position_code: String,
-    #[serde(rename = "lastSeasonId")]
-    last_season_id: Option<String>,
-    active: bool,
+struct UpdateOptions<'a> {
+    range: SeasonRange,
+    ledger: &'a Path,
+    report: Option<&'a Path>,
 }
 
-#[derive(Deserialize)]
-struct PlayerDetails {
-    #[serde(rename = "playerId")]
-    player_id: u64,
-    #[serde(rename = "firstName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    first_name: String,
-    #[serde(rename = "lastName")]
-    #[serde(deserialize_with = "deserialize_name_field")]
-    last_name: String,
-    #[serde(rename = "birthDate")]
-    birth_date: Option<String>,
-    #[serde(rename = "birthCity")]
-    #[serde(deserialize_with = "deserialize_optional_name_field")]
-    birth_city: Option<String>,
-    #[serde(rename = "birthCountry")]
-    birth_country: Option<String>,
-    position: Option<String>,
-    #[serde(rename = "heightInInches")]
-    height_in_inches: Option<u32>,
-    #[serde(rename = "weightInPounds")]
-    weight_in_pounds: Option<u32>,
-    #[serde(rename = "currentTeamAbbrev")]
-    current_team_abbrev: Option<String>,
-    #[serde(rename = "seasonTotals")]
-    season_totals: Option<Vec<SeasonTotal>>,
+async fn update(settings: &Settings, database_path: &str, output: &str, options: UpdateOptions<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let range = options.range;
+    let mut database = PlayerDatabase::from_json(&fs::read_to_string(database_path)?)?;
+    println!("🔄 Updating {} ({} players) with seasons {} to {}", database_path, database.players.len(), range.start_year, range.end_year);
+    let previous_build = database.generated_at.clone();
+    let known_players: BTreeSet<String> = database.players.keys().cloned().collect();
+
+    // Only players still playing in the range can have joined a team in it
+    let api = NhlApi::new(settings)?;
+    let players = api.fetch_all_players().await?;
+    let total = players.len();
+    let players: Vec<PlayerSearchResult> = players.into_iter().filter(|player| player.active_since(range.start_year)).collect();
+    println!("🟢 {} of {} players are active or played since {}-{}", players.len(), total, range.start_year, range.start_year + 1);
+    let mut state = BuildState::new(range, chrono::Utc::now().to_rfc3339(), players);
+    builder::collect_team_players(&api, settings, &mut state, None).await?;
+
+    // New teams are appended after each roster's existing players
+    let additions = builder::merge_into(&mut database, &state);
+    let new_players: BTreeSet<String> = additions.values().flatten()
+        .filter(|id| !known_players.contains(*id))
+        .cloned()
+        .collect();
+    for season in range.seasons() {
+        if !database.seasons_covered.contains(&season) {
+            database.seasons_covered.push(season);
+        }
+    }
+    database.seasons_covered.sort();
+    database.generated_at = state.generated_at.clone();
+    builder::print_summary(&database);
+    builder::print_additions(&database, &additions, &new_players, &previous_build);
+    if let Some(path) = options.report {
+        let added: BTreeMap<&String, Vec<&PlayerInfo>> = additions.iter()
+            .map(|(team, ids)| (team, ids.iter().filter_map(|id| database.player(id)).collect()))
+            .collect();
+        let report = serde_json::json!({
+            "previous_build": previous_build,
+            "generated_at": database.generated_at,
+            "new_players": new_players,
+            "added": added
+        });
+        fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("📋 Report saved to: {}", path.display());
+    }
+    write_database(&database, output)?;
+    state.record_failures(options.ledger)
 }
 
-#[derive(Deserialize)]
-struct SeasonTotal {
-    season: u32,
-    #[serde(rename = "teamName")]
-    #[serde(deserialize_with = "deserialize_optional_name_field")]
-    team_name: Option<String>,
-}
+async fn retry_failed(settings: &Settings, database_path: &str, output: &str, ledger: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut database = PlayerDatabase::from_json(&fs::read_to_string(database_path)?)?;
+    let failed = failures::load(ledger)?;
+    if failed.is_empty() {
+        println!("✅ {} lists no failed players", ledger.display());
+        return Ok(());
+    }
+    // The players are looked up for the same seasons as the rest of the database
+    let range = SeasonRange::covered_by(&database)
+        .ok_or_else(|| format!("{} doesn't list the seasons it covers", database_path))?;
+    println!("🔁 Retrying {} failed players from {} for seasons {} to {}", failed.len(), ledger.display(), range.start_year, range.end_year);
 
-#[derive(Serialize, Clone, Hash, PartialEq, Eq)]
-struct PlayerInfo {
-    id: String,
-    name: String,
-    birth_date: Option<String>,
-    birth_place: Option<String>, // "City, Country" format
-    position: Option<String>,
+    let players = failed.into_iter()
+        .map(|failure| PlayerSearchResult { player_id: failure.id, name: failure.name, last_season_id: None, active: false })
+        .collect();
+    let api = NhlApi::new(settings)?;
+    let mut state = BuildState::new(range, chrono::Utc::now().to_rfc3339(), players);
+    builder::collect_team_players(&api, settings, &mut state, None).await?;
+
+    builder::merge_into(&mut database, &state);
+    database.generated_at = state.generated_at.clone();
+    builder::print_summary(&database);
+    write_database(&database, output)?;
+    state.record_failures(ledger)
 }
 
-#[derive(Serialize)]
-struct PlayerDatabase {
-    teams: HashMap<String, Vec<PlayerInfo>>,
-    generated_at: String,
-    seasons_covered: Vec<String>,
-}
+fn solve_teams(database_path: &str, teams: &[String], samples: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let database = PlayerDatabase::from_json(&fs::read_to_string(database_path)?)?;
+    let teams: Vec<String> = teams.iter().map(|team| team.trim().to_uppercase()).collect();
 
-// List of current NHL team codes
-const CURRENT_TEAM_CODES: [&str; 32] = [
-    "ANA", "BOS", "BUF", "CGY", "CAR", "CHI", "COL", "CBJ", "DAL", "DET",
-    "EDM", "FLA", "LAK", "MIN", "MTL", "NSH", "NJD", "NYI", "NYR", "OTT",
-    "PHI", "PIT", "SJS", "SEA", "STL", "TBL", "TOR", "UTA", "VAN", "VGK",
-    "WSH", "WPG"
-];
-
-// Historical team codes that need to be consolidated into current teams
-const HISTORICAL_TEAM_CODES: [&str; 11] = [
-    "ATL",  // Atlanta Thrashers → Winnipeg Jets
-    "HFD",  // Hartford Whalers → Carolina Hurricanes  
-    "QUE",  // Quebec Nordiques → Colorado Avalanche
-    "MNS",  // Minnesota North Stars → Dallas Stars
-    "CLR",  // Colorado Rockies → New Jersey Devils
-    "KCS",  // Kansas City Scouts → New Jersey Devils (via Colorado)
-    "ATF",  // Atlanta Flames → Calgary Flames
-    "WPG1", // Original Winnipeg Jets → Arizona Coyotes (now Utah)
-    "PHX",  // Phoenix Coyotes → Utah Hockey Club
-    "ARI",  // Arizona Coyotes → Utah Hockey Club
-    "MIG",  // Mighty Ducks → Anaheim Ducks
-];
-
-// Team relocation mapping: historical_code -> current_code
-fn get_team_mapping() -> HashMap<&'static str, &'static str> {
-    let mut mapping = HashMap::new();
-    
-    // Major relocations and name changes
-    mapping.insert("ATL", "WPG");     // Atlanta Thrashers → Winnipeg Jets (2011)
-    mapping.insert("HFD", "CAR");     // Hartford Whalers → Carolina Hurricanes (1997)
-    mapping.insert("QUE", "COL");     // Quebec Nordiques → Colorado Avalanche (1995)
-    mapping.insert("MNS", "DAL");     // Minnesota North Stars → Dallas Stars (1993)
-    mapping.insert("CLR", "NJD");     // Colorado Rockies → New Jersey Devils (1982)
-    mapping.insert("KCS", "NJD");     // Kansas City Scouts → New Jersey Devils (via Colorado, 1976)
-    mapping.insert("ATF", "CGY");     // Atlanta Flames → Calgary Flames (1980)
-    mapping.insert("WPG1", "UTA");    // Original Winnipeg Jets → Arizona → Utah (1996)
-    mapping.insert("PHX", "UTA");     // Phoenix Coyotes → Utah Hockey Club (2024)
-    mapping.insert("ARI", "UTA");     // Arizona Coyotes → Utah Hockey Club (2024)
-    
-    // Name changes (same location)
-    mapping.insert("MIG", "ANA");     // Mighty Ducks → Anaheim Ducks (2006)
-    
-    // Add current teams to themselves (identity mapping)
-    for &team in CURRENT_TEAM_CODES.iter() {
-        mapping.insert(team, team);
+    let mut team_players = Vec::new();
+    for team in &teams {
+        let ids = database.teams.get(team)
+            .ok_or_else(|| format!("Team {} is not in {}", team, database_path))?;
+        team_players.push(ids.clone());
     }
-    
-    mapping
-}
 
-// Get all team codes to fetch (current + historical)
-fn get_all_team_codes() -> Vec<&'static str> {
-    let mut codes = CURRENT_TEAM_CODES.to_vec();
-    codes.extend_from_slice(&HISTORICAL_TEAM_CODES);
-    codes
-}
+    println!("🧩 Solving minimum cover for {}", teams.join(", "));
+    let solution = solver::solve(&team_players, samples)?;
 
-async fn fetch_all_players(client: &reqwest::Client) -> Result<Vec<PlayerSearchResult>, Box<dyn std::error::Error>> {
-    let url = "https://search.d3.nhle.com/api/v1/search/player?culture=en-us&limit=24000&q=*";
-    
-    println!("🔍 Fetching all players from NHL search API...");
-    
-    let response = client
-        .get(url)
-        .header("User-Agent", "NHL Player Database Generator 1.0")
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
-        let players: Vec<PlayerSearchResult> = response.json().await?;
-        println!("✅ Found {} players in search results", players.len());
-        Ok(players)
+    println!("✅ Minimum players needed: {}", solution.min_players);
+    if solution.exhaustive {
+        println!("   Optimal covers: {}", solution.optimal_covers);
     } else {
-        Err(format!("HTTP {} for player search", response.status()).into())
+        println!("   Optimal covers: at least {} (enumeration limit reached)", solution.optimal_covers);
     }
-}
-
-async fn fetch_player_details(client: &reqwest::Client, player_id: &str) -> Result<PlayerDetails, Box<dyn std::error::Error>> {
-    let url = format!("https://api-web.nhle.com/v1/player/{player_id}/landing");
-    
-    println!("   Fetching details for player ID: {}", player_id);
-    let response = client
-        .get(&url)
-        .header("User-Agent", "NHL Player Database Generator 1.0")
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
-        let player_details: PlayerDetails = response.json().await?;
-        Ok(player_details)
-    } else {
-        Err(format!("HTTP {} for player {}", response.status(), player_id).into())
+    for (index, cover) in solution.samples.iter().enumerate() {
+        let players: Vec<String> = cover.iter()
+            .map(|id| format!("{} ({})", database.player(id).map(|p| p.name.as_str()).unwrap_or("?"), id))
+            .collect();
+        println!("   {}. {}", index + 1, players.join(", "));
     }
-}
 
-async fn build_database_from_player_search(
-    client: &reqwest::Client,
-    delay_ms: u64,
-    start_year: u32,
-    end_year: u32,
-) -> Result<HashMap<String, HashSet<PlayerInfo>>, Box<dyn std::error::Error>> {
-    let all_players = fetch_all_players(client).await?;
-    let team_mapping = get_team_mapping();
-    let mut consolidated_database: HashMap<String, HashSet<PlayerInfo>> = HashMap::new();
-    
-    let team_codes: HashMap<&str, &str> = [
-        ("Anaheim Ducks", "ANA"), ("Boston Bruins", "BOS"), ("Buffalo Sabres", "BUF"),
-        ("Calgary Flames", "CGY"), ("Carolina Hurricanes", "CAR"), ("Chicago Blackhawks", "CHI"),
-        ("Colorado Avalanche", "COL"), ("Columbus Blue Jackets", "CBJ"), ("Dallas Stars", "DAL"),
-        ("Detroit Red Wings", "DET"), ("Edmonton Oilers", "EDM"), ("Florida Panthers", "FLA"),
-        ("Los Angeles Kings", "LAK"), ("Minnesota Wild", "MIN"), ("Montréal Canadiens", "MTL"),
-        ("Nashville Predators", "NSH"), ("New Jersey Devils", "NJD"), ("New York Islanders", "NYI"),
-        ("New York Rangers", "NYR"), ("Ottawa Senators", "OTT"), ("Philadelphia Flyers", "PHI"),
-        ("Pittsburgh Penguins", "PIT"), ("San Jose Sharks", "SJS"), ("Seattle Kraken", "SEA"),
-        ("St. Louis Blues", "STL"), ("Tampa Bay Lightning", "TBL"), ("Toronto Maple Leafs", "TOR"),
-        ("Utah Hockey Club", "UTA"), ("Vancouver Canucks", "VAN"), ("Vegas Golden Knights", "VGK"),
-        ("Washington Capitals", "WSH"), ("Winnipeg Jets", "WPG"),
-        // Historical team names that map to team codes
-        ("Atlanta Thrashers", "ATL"), ("Hartford Whalers", "HFD"), ("Quebec Nordiques", "QUE"),
-        ("Minnesota North Stars", "MNS"), ("Colorado Rockies", "CLR"), ("Kansas City Scouts", "KCS"),
-        ("Atlanta Flames", "ATF"), ("Phoenix Coyotes", "PHX"), ("Arizona Coyotes", "ARI"),
-        ("Mighty Ducks of Anaheim", "MIG"), ("Winnipeg Jets (1979)", "WPG1"),
-    ].iter().cloned().collect();
-
-    // Initialize current teams in the database
-    for &current_team in CURRENT_TEAM_CODES.iter() {
-        consolidated_database.insert(current_team.to_string(), HashSet::new());
-    }
-    
-    println!("🏒 Processing {} players to build team associations...", all_players.len());
-    println!("📅 Including seasons {}-{} to {}-{}", start_year, start_year + 1, end_year, end_year + 1);
-    
-    let total_players = all_players.len();
-    let mut processed_count = 0;
-    let mut players_with_teams = 0;
-    let mut api_errors = 0;
-    
-    for player in &all_players {
-        sleep(Duration::from_millis(delay_ms)).await;
-        processed_count += 1;
-        
-        // Progress indicator every 100 players
-        if processed_count % 100 == 0 {
-            println!("📊 Progress: {}/{} players processed ({:.1}%), {} with teams, {} errors", 
-                     processed_count, total_players, 
-                     (processed_count as f64 / total_players as f64) * 100.0,
-                     players_with_teams, api_errors);
-        }
-        
-        match fetch_player_details(client, &player.player_id).await {
-            Ok(details) => {
-                let full_name = format!("{} {}", details.first_name, details.last_name);
-                let mut player_teams = HashSet::new();
-                println!("   Processing player: {} (ID: {})", full_name, player.player_id);
-                
-                // Create PlayerInfo object
-                let birth_place = match (&details.birth_city, &details.birth_country) {
-                    (Some(city), Some(country)) => Some(format!("{}, {}", city, country)),
-                    (None, Some(country)) => Some(country.clone()),
-                    _ => None,
-                };
-                
-                let player_info = PlayerInfo {
-                    id: details.player_id.to_string(),
-                    name: full_name.clone(),
-                    birth_date: details.birth_date.clone(),
-                    birth_place,
-                    position: details.position.clone(),
-                };
-                
-                // Extract teams from season totals
-                if let Some(season_totals) = &details.season_totals {
-                    for season_total in season_totals {
-                        // Filter by season range (convert season format)
-                        let season_start_year = season_total.season / 10000;
-                        if season_start_year >= start_year && season_start_year <= end_year {
-                            if let Some(team_full_name) = &season_total.team_name {
-                                if let Some(&team_code) = team_codes.get(team_full_name.as_str()) {
-                                    player_teams.insert(team_code.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // Map teams to current teams and add player
-                if !player_teams.is_empty() {
-                    players_with_teams += 1;
-                    for team_code in &player_teams {
-                        if let Some(&current_team) = team_mapping.get(team_code.as_str()) {
-                            if let Some(team_players) = consolidated_database.get_mut(current_team) {
-                                team_players.insert(player_info.clone());
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                println!("⚠️  Failed to fetch player details (ID: {}): {}", player.player_id, e);
-                api_errors += 1;
-            }
-        }
-    }
-    
-    println!("✅ Completed processing {} players", processed_count);
-    println!("   Players with team data: {}", players_with_teams);
-    println!("   API errors: {}", api_errors);
-    
-    Ok(consolidated_database)
+    Ok(())
 }
 
-async fn build_database_legacy(
-    client: &reqwest::Client,
-    seasons: &[String],
-    delay_ms: u64,
-    include_games: bool,
-) -> Result<HashMap<String, HashSet<String>>, Box<dyn std::error::Error>> {
-    let team_mapping = get_team_mapping();
-    let all_team_codes = get_all_team_codes();
-    let mut consolidated_database: HashMap<String, HashSet<String>> = HashMap::new();
-    
-    // Initialize current teams in the database
-    for &current_team in CURRENT_TEAM_CODES.iter() {
-        consolidated_database.insert(current_team.to_string(), HashSet::new());
-    }
-    
-    let total_requests = all_team_codes.len() * seasons.len();
-    let mut completed_requests = 0;
-    
-    for (team_idx, &team_code) in all_team_codes.iter().enumerate() {
-        let mut team_players = HashSet::new();
-        let mut roster_players = HashSet::new();
-        let mut game_players = HashSet::new();
-        
-        for (_season_idx, season) in seasons.iter().enumerate() {
-            // Fetch roster data
-            match fetch_roster(client, team_code, season).await {
-                Ok(roster_data) => {
-                    let players = extract_players(&roster_data);
-                    for player in players {
-                        roster_players.insert(player.clone());
-                        team_players.insert(player);
-                    }
-                    if !roster_players.is_empty() {
-                        println!("✓ {}/{} - Roster: {} players", team_code, season, roster_players.len());
-                    }
-                }
-                Err(e) => {
-                    eprintln!("⚠️  Failed to fetch roster {}/{}: {}", team_code, season, e);
-                }
-            }
-            
-            // Fetch game data if enabled
-            if include_games {
-                match fetch_players_from_games(client, team_code, season, delay_ms).await {
-                    Ok(season_game_players) => {
-                        let mut new_players = 0;
-                        for player in &season_game_players {
-                            if !roster_players.contains(player) {
-                                new_players += 1;
-                                game_players.insert(player.clone());
-                                team_players.insert(player.clone());
-                            }
-                        }
-                        if new_players > 0 {
-                            println!("  📋 {}/{} - Games: {} additional players not in roster", team_code, season, new_players);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("⚠️  Failed to fetch game data {}/{}: {}", team_code, season, e);
-                    }
-                }
-            }
-            
-            completed_requests += 1;
-            
-            // Progress indicator
-            if completed_requests % 20 == 0 {
-                println!("Progress: {}/{} requests completed ({:.1}%)", 
-                    completed_requests, total_requests, 
-                    (completed_requests as f64 / total_requests as f64) * 100.0);
-            }
-            
-            // Rate limiting - sleep between requests
-            sleep(Duration::from_millis(delay_ms)).await;
-        }
-        
-        // Consolidate players into current team
-        if let Some(&current_team) = team_mapping.get(team_code) {
-            if let Some(current_team_players) = consolidated_database.get_mut(current_team) {
-                for player in &team_players {
-                    current_team_players.insert(player.clone());
-                }
-            }
-            
-            let roster_count = roster_players.len();
-            let game_count = game_players.len();
-            let total_count = team_players.len();
-            
-            if include_games && game_count > 0 {
-                println!("🏒 Completed {} ({}/{}) - {} total players ({} roster + {} from games) → consolidated into {}", 
-                    team_code, team_idx + 1, all_team_codes.len(), total_count, roster_count, game_count, current_team);
-            } else {
-                println!("🏒 Completed {} ({}/{}) - {} unique players → consolidated into {}", 
-                    team_code, team_idx + 1, all_team_codes.len(), total_count, current_team);
-            }
-        } else {
-            eprintln!("⚠️  No mapping found for team code: {}", team_code);
-        }
+fn split_dataset(database_path: &str, version: u32, out_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let database = PlayerDatabase::from_json(&fs::read_to_string(database_path)?)?;
+    let prefix = DatasetVersion::key_prefix_for(version);
+    let entries = database.kv_entries()?;
+
+    fs::create_dir_all(out_dir)?;
+    let out_dir = std::path::Path::new(out_dir);
+    fs::write(out_dir.join(DatasetVersion::blob_key_for(version)), serde_json::to_string(&database)?)?;
+    for (key, value) in &entries {
+        fs::write(out_dir.join(format!("{}{}", prefix, key)), value)?;
     }
-    
-    Ok(consolidated_database)
+
+    println!("✅ Wrote dataset version {} ({} entries) to {}", version, entries.len() + 1, out_dir.display());
+    println!("   Players: {}", database.players.len());
+    println!("   Teams: {}", database.teams.len());
+    Ok(())
 }
 
-async fn fetch_roster(client: &reqwest::Client, team_code: &str, season: &str) -> Result<RosterData, Box<dyn std::error::Error>> {
-    let url = format!("https://api-web.nhle.com/v1/roster/{}/{}", team_code, season);
-    
-    let response = client
-        .get(&url)
-        .header("User-Agent", "NHL Player Database Generator 1.0")
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
-        let roster_data: RosterData = response.json().await?;
-        Ok(roster_data)
+async fn upload_calendar(file: &str, endpoint: &str, token: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(file)?;
+    let calendar: PuzzleCalendar = if file.ends_with(".toml") {
+        toml::from_str(&contents)?
     } else {
-        Err(format!("HTTP {} for {}/{}", response.status(), team_code, season).into())
-    }
-}
+        serde_json::from_str(&contents)?
+    };
 
-async fn fetch_team_schedule(client: &reqwest::Client, team_code: &str, season: &str) -> Result<ScheduleResponse, Box<dyn std::error::Error>> {
-    // Try different API endpoint formats
-    let urls = vec![
-        format!("https://api-web.nhle.com/v1/club-schedule-season/{}/{}", team_code, season),
-        format!("https://api-web.nhle.com/v1/schedule/{}/{}", team_code, season),
-        format!("https://statsapi.web.nhl.com/api/v1/teams/{}/schedule?season={}", team_code, season),
-    ];
-    
-    for url in urls {
-        let response = client
-            .get(&url)
-            .header("User-Agent", "NHL Player Database Generator 1.0")
-            .send()
-            .await?;
-        
-        if response.status().is_success() {
-            match response.json::<ScheduleResponse>().await {
-                Ok(schedule_data) => return Ok(schedule_data),
-                Err(_) => continue, // Try next URL format
-            }
-        }
+    // Catch typos before anything is sent; the service checks the teams
+    for entry in &calendar.puzzles {
+        chrono::NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d")
+            .map_err(|_| format!("'{}' in {} is not a YYYY-MM-DD date", entry.date, file))?;
     }
-    
-    Err(format!("All schedule API endpoints failed for {}/{}", team_code, season).into())
-}
 
-async fn fetch_game_details(client: &reqwest::Client, game_id: u64) -> Result<GameDetails, Box<dyn std::error::Error>> {
-    let url = format!("https://api-web.nhle.com/v1/gamecenter/{}/boxscore", game_id);
-    
-    let response = client
-        .get(&url)
-        .header("User-Agent", "NHL Player Database Generator 1.0")
+    println!("📅 Uploading {} curated puzzles to {}", calendar.puzzles.len(), endpoint);
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/puzzles", endpoint.trim_end_matches('/')))
+        .bearer_auth(token)
+        .json(&calendar)
         .send()
         .await?;
-    
-    if response.status().is_success() {
-        let game_data: GameDetails = response.json().await?;
-        Ok(game_data)
-    } else {
-        Err(format!("HTTP {} for game {}", response.status(), game_id).into())
-    }
-}
-
-async fn fetch_players_from_games(
-    client: &reqwest::Client, 
-    team_code: &str, 
-    season: &str,
-    delay_ms: u64
-) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
-    let mut game_players = HashSet::new();
-    
-    // Fetch team schedule for the season
-    match fetch_team_schedule(client, team_code, season).await {
-        Ok(schedule) => {
-            println!("    📅 Found {} games for {}/{}", schedule.games.len(), team_code, season);
-            
-            // Limit to first 10 games for now to avoid too many requests
-            let games_to_check = schedule.games.iter().take(10);
-            
-            for game in games_to_check {
-                // Check if this team was playing in this game
-                if game.away_team.abbrev == team_code || game.home_team.abbrev == team_code {
-                    sleep(Duration::from_millis(delay_ms)).await;
-                    
-                    match fetch_game_details(client, game.id).await {
-                        Ok(game_details) => {
-                            // Extract players from the team we're interested in
-                            let team_details = if game.away_team.abbrev == team_code {
-                                &game_details.away_team
-                            } else {
-                                &game_details.home_team
-                            };
-                            
-                            if let Some(team_data) = team_details {
-                                // Extract skaters
-                                if let Some(skaters) = &team_data.skaters {
-                                    for player in skaters {
-                                        let full_name = format!("{} {}", player.first_name, player.last_name);
-                                        game_players.insert(full_name);
-                                    }
-                                }
-                                
-                                // Extract goalies
-                                if let Some(goalies) = &team_data.goalies {
-                                    for player in goalies {
-                                        let full_name = format!("{} {}", player.first_name, player.last_name);
-                                        game_players.insert(full_name);
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("    ⚠️  Failed to fetch game {}: {}", game.id, e);
-                        }
-                    }
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("    ⚠️  Failed to fetch schedule for {}/{}: {}", team_code, season, e);
-        }
+    if !response.status().is_success() {
+        return Err(format!("Upload failed with HTTP {}: {}", response.status(), response.text().await?).into());
     }
-    
-    Ok(game_players)
-}
 
-fn extract_players(roster_data: &RosterData) -> Vec<String> {
-    let mut players = Vec::new();
-    
-    if let Some(forwards) = &roster_data.forwards {
-        for player in forwards {
-            players.push(format!("{} {}", player.first_name, player.last_name));
-        }
+    let result: serde_json::Value = response.json().await?;
+    let empty = Vec::new();
+    for stored in result["stored"].as_array().unwrap_or(&empty) {
+        println!("✅ {} ({}): {}", stored["date"].as_str().unwrap_or("?"),
+                 stored["difficulty"].as_str().unwrap_or("?"), stored["teams"]);
     }
-    
-    if let Some(defensemen) = &roster_data.defensemen {
-        for player in defensemen {
-            players.push(format!("{} {}", player.first_name, player.last_name));
-        }
+    let rejected = result["rejected"].as_array().unwrap_or(&empty);
+    for entry in rejected {
+        println!("❌ {}: {}", entry["date"].as_str().unwrap_or("?"), entry["message"].as_str().unwrap_or("?"));
     }
-    
-    if let Some(goalies) = &roster_data.goalies {
-        for player in goalies {
-            players.push(format!("{} {}", player.first_name, player.last_name));
-        }
+    if !rejected.is_empty() {
+        return Err(format!("{} of {} puzzles were rejected", rejected.len(), calendar.puzzles.len()).into());
     }
-    
-    players
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    
-    println!("🏒 NHL Player Database Generator");
-    println!("Output file: {}", cli.output);
-    println!("Rate limit delay: {}ms", cli.delay);
-    
-    // Create HTTP client with timeout and connection pooling
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
-        .pool_max_idle_per_host(2)
-        .build()?;
-    
-    let consolidated_database = if cli.use_player_search {
-        println!("🚀 Using new player search API approach");
-        println!("📅 Season filter: {}-{} to {}-{}", cli.start_year, cli.start_year + 1, cli.end_year, cli.end_year + 1);
-        
-        build_database_from_player_search(&client, cli.delay, cli.start_year, cli.end_year).await?
-    } else {
-        // TODO: Update legacy function to return PlayerInfo format
-        println!("❌ Legacy approach temporarily disabled. Please use --use-player-search flag.");
-        std::process::exit(1);
-    };
-    
-    // Generate seasons list for database metadata
-    let seasons: Vec<String> = (cli.start_year..=cli.end_year)
-        .map(|year| format!("{}{}", year, year + 1))
-        .collect();
+    let settings = Settings { delay: cli.delay, cache_dir: cli.cache_dir, concurrency: cli.concurrency, offline: cli.offline };
 
-    // Convert HashSet to Vec for serialization and create final database structure
-    let teams: HashMap<String, Vec<PlayerInfo>> = consolidated_database
-        .into_iter()
-        .map(|(team, players)| {
-            let mut player_list: Vec<PlayerInfo> = players.into_iter().collect();
-            player_list.sort_by(|a, b| a.name.cmp(&b.name)); // Sort players alphabetically by name
-            (team, player_list)
-        })
-        .collect();
-    
-    let database = PlayerDatabase {
-        teams,
-        generated_at: chrono::Utc::now().to_rfc3339(),
-        seasons_covered: seasons,
-    };
-    
-    // Calculate total unique players across all teams
-    let total_players: usize = database.teams.values().map(|players| players.len()).sum();
-    
-    println!("\n📊 Database Summary:");
-    println!("   Teams: {}", database.teams.len());
-    println!("   Total players: {}", total_players);
-    println!("   Seasons covered: {} to {}", cli.start_year, cli.end_year);
-    
-    if cli.use_player_search {
-        println!("   Data source: NHL Player Search API (comprehensive)");
-        println!("   Method: Individual player career analysis");
-    } else if cli.include_games {
-        println!("   Data sources: Team rosters + game-by-game player appearances");
-        println!("   Note: Game data limited to first 10 games per team/season for API efficiency");
-    } else {
-        println!("   Data sources: Team rosters only");
+    match &cli.command {
+        Command::Build { output, start_year, end_year, resume, failures } => {
+            build(&settings, output, SeasonRange { start_year: *start_year, end_year: *end_year }, *resume, failures).await
+        }
+        Command::Update { database, output, start_year, end_year, failures, report } => {
+            let output = output.as_deref().unwrap_or(database);
+            let end_year = end_year.unwrap_or_else(|| builder::season_start_year(chrono::Local::now().date_naive()));
+            let options = UpdateOptions {
+                range: SeasonRange { start_year: start_year.unwrap_or(end_year), end_year },
+                ledger: failures,
+                report: report.as_deref(),
+            };
+            update(&settings, database, output, options).await
+        }
+        Command::RetryFailed { database, output, failures } => {
+            retry_failed(&settings, database, output.as_deref().unwrap_or(database), failures).await
+        }
+        Command::Validate { database } => inspect::validate(database),
+        Command::Diff { old, new, limit } => inspect::diff(old, new, *limit),
+        Command::Stats { database, top } => inspect::stats(database, *top),
+        Command::Query { database, player, teams } => inspect::query(database, player.as_deref(), teams),
+        Command::Solve { database, teams, samples } => solve_teams(database, teams, *samples),
+        Command::UploadCalendar { file, endpoint, token } => upload_calendar(file, endpoint, token).await,
+        Command::Publish { database, version, promote, local, force, store_id, api_token, endpoint, token } => {
+            let database = PlayerDatabase::from_json(&fs::read_to_string(database)?)?;
+            if let Some(fastly_toml) = local {
+                return publish::publish_local(&database, *version, *force, fastly_toml);
+            }
+            let target = publish::RemoteTarget {
+                store_id: store_id.as_deref().ok_or("--store-id or FASTLY_KV_STORE_ID is required")?,
+                api_token: api_token.as_deref().ok_or("--api-token or FASTLY_API_TOKEN is required")?,
+                endpoint,
+                admin_token: token.as_deref().ok_or("--token or JOURNEYMAN_ADMIN_TOKEN is required")?,
+            };
+            publish::publish(&database, *version, *promote, target).await
+        }
+        Command::SplitDataset { database, version, out_dir } => split_dataset(database, *version, out_dir),
     }
-    
-    // Write to JSON file
-    let json = serde_json::to_string_pretty(&database)?;
-    fs::write(&cli.output, json)?;
-    
-    println!("✅ Database saved to: {}", cli.output);
-    println!("📈 File size: {:.2} KB", fs::metadata(&cli.output)?.len() as f64 / 1024.0);
-    
-    Ok(())
 }
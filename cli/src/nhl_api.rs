@@ -0,0 +1,273 @@
+//! The parts of the NHL APIs the database is built from: the player search index, which lists
+//! every player, and each player's landing page, which has their bio and season-by-season teams.
+
+use crate::cache::ResponseCache;
+use crate::rate_limit::RateLimiter;
+use crate::Settings;
+use journeyman_core::dataset::PlayerInfo;
+use journeyman_core::teams;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const USER_AGENT: &str = "NHL Player Database Generator 1.0";
+const PLAYER_SEARCH_URL: &str = "https://search.d3.nhle.com/api/v1/search/player?culture=en-us&limit=24000&q=*";
+const PLAYER_SEARCH_KEY: &str = "player-search";
+// Attempts per request, and the backoff before the second
+const MAX_ATTEMPTS: u32 = 5;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+// Longest a Retry-After is honoured for; anything longer fails the request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+// Custom deserializer to extract the "default" field
+fn deserialize_name_field<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let name_field = NameField::deserialize(deserializer)?;
+    Ok(name_field.default)
+}
+
+// Custom deserializer for optional name fields
+fn deserialize_optional_name_field<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let opt_name_field = Option::<NameField>::deserialize(deserializer)?;
+    Ok(opt_name_field.map(|field| field.default))
+}
+
+#[derive(Deserialize)]
+struct NameField {
+    #[serde(rename = "default")]
+    default: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSearchResult {
+    #[serde(rename = "playerId")]
+    pub player_id: String,
+    pub name: String,
+    /// e.g. "20232024"
+    #[serde(rename = "lastSeasonId")]
+    pub last_season_id: Option<String>,
+    /// On a current NHL roster
+    #[serde(default)]
+    pub active: bool,
+}
+
+impl PlayerSearchResult {
+    /// Starting year of the player's last season, if the index has it.
+    pub fn last_season_start(&self) -> Option<u32> {
+        self.last_season_id.as_deref().and_then(|season| season.get(..4)).and_then(|year| year.parse().ok())
+    }
+
+    /// Active, or played in a season starting in `year` or later. Players the index has no last
+    /// season for only count if they're active.
+    pub fn active_since(&self, year: u32) -> bool {
+        self.active || self.last_season_start().is_some_and(|last| last >= year)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PlayerDetails {
+    #[serde(rename = "playerId")]
+    pub player_id: u64,
+    #[serde(rename = "firstName")]
+    #[serde(deserialize_with = "deserialize_name_field")]
+    pub first_name: String,
+    #[serde(rename = "lastName")]
+    #[serde(deserialize_with = "deserialize_name_field")]
+    pub last_name: String,
+    #[serde(rename = "birthDate")]
+    pub birth_date: Option<String>,
+    #[serde(rename = "birthCity")]
+    #[serde(deserialize_with = "deserialize_optional_name_field")]
+    pub birth_city: Option<String>,
+    #[serde(rename = "birthCountry")]
+    pub birth_country: Option<String>,
+    pub position: Option<String>,
+    #[serde(rename = "seasonTotals")]
+    pub season_totals: Option<Vec<SeasonTotal>>,
+}
+
+#[derive(Deserialize)]
+pub struct SeasonTotal {
+    /// e.g. 20232024
+    pub season: u32,
+    #[serde(rename = "teamName")]
+    #[serde(deserialize_with = "deserialize_optional_name_field")]
+    pub team_name: Option<String>,
+}
+
+impl PlayerDetails {
+    pub fn player_info(&self) -> PlayerInfo {
+        let birth_place = match (&self.birth_city, &self.birth_country) {
+            (Some(city), Some(country)) => Some(format!("{}, {}", city, country)),
+            (None, Some(country)) => Some(country.clone()),
+            _ => None,
+        };
+
+        PlayerInfo {
+            id: self.player_id.to_string(),
+            name: format!("{} {}", self.first_name, self.last_name),
+            birth_date: self.birth_date.clone(),
+            birth_place,
+            position: self.position.clone(),
+        }
+    }
+
+    /// Current codes of the teams the player played for in seasons starting between `start_year`
+    /// and `end_year`. Relocated and renamed teams count as the franchise they are today.
+    pub fn teams_between(&self, start_year: u32, end_year: u32) -> HashSet<String> {
+        self.season_totals.iter()
+            .flatten()
+            .filter(|season_total| (start_year..=end_year).contains(&(season_total.season / 10000)))
+            .filter_map(|season_total| {
+                let team_name = season_total.team_name.as_deref()?;
+                teams::current_code_for_season(team_name, season_total.season / 10000)
+            })
+            .map(|code| code.to_string())
+            .collect()
+    }
+}
+
+/// Fetches from the NHL APIs, keeping every response in the cache, or with `offline` reads
+/// only from the cache.
+///
+/// Requests share one rate limiter however many workers clone this. Timeouts, 429s and 5xx
+/// responses are retried with exponential backoff, or after the response's `Retry-After`, and
+/// 429s and 503s also slow the limiter down for everyone.
+#[derive(Clone)]
+pub struct NhlApi {
+    client: reqwest::Client,
+    cache: ResponseCache,
+    limiter: Arc<RateLimiter>,
+    offline: bool,
+}
+
+// Why an attempt failed, and whether it's worth another
+enum AttemptError {
+    Retry { error: String, retry_after: Option<Duration> },
+    Fail(Box<dyn std::error::Error + Send + Sync>),
+}
+
+// How long a 429 or 5xx response asks us to wait, in seconds or as an HTTP date
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.to_utc() - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+impl NhlApi {
+    pub fn new(settings: &Settings) -> Result<NhlApi, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .pool_max_idle_per_host(2)
+            .build()?;
+        // Reading the cache needs no rate limit
+        let interval = if settings.offline { Duration::ZERO } else { Duration::from_millis(settings.delay) };
+        Ok(NhlApi {
+            client,
+            cache: ResponseCache::new(&settings.cache_dir),
+            limiter: Arc::new(RateLimiter::new(interval, settings.concurrency)),
+            offline: settings.offline,
+        })
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    async fn attempt(&self, url: &str) -> Result<String, AttemptError> {
+        self.limiter.acquire().await;
+        let response = match self.client.get(url).header("User-Agent", USER_AGENT).send().await {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() || e.is_connect() => return Err(AttemptError::Retry { error: e.to_string(), retry_after: None }),
+            Err(e) => return Err(AttemptError::Fail(e.into())),
+        };
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            let interval = self.limiter.slow_down();
+            println!("🐢 HTTP {} for {}; slowing to one request every {}ms", status, url, interval.as_millis());
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(AttemptError::Retry { error: format!("HTTP {} for {}", status, url), retry_after: retry_after(&response) });
+        }
+        if !status.is_success() {
+            return Err(AttemptError::Fail(format!("HTTP {} for {}", status, url).into()));
+        }
+
+        let body = response.text().await.map_err(|e| AttemptError::Retry { error: e.to_string(), retry_after: None })?;
+        self.limiter.speed_up();
+        Ok(body)
+    }
+
+    // The body of a successful GET, stored in the cache under `key`
+    async fn get(&self, key: &str, url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut backoff = FIRST_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.attempt(url).await {
+                Ok(body) => {
+                    self.cache.put(key, url, &body).await?;
+                    return Ok(body);
+                }
+                Err(AttemptError::Fail(e)) => return Err(e),
+                Err(AttemptError::Retry { error, .. }) if attempt == MAX_ATTEMPTS => {
+                    return Err(format!("{} (gave up after {} attempts)", error, MAX_ATTEMPTS).into());
+                }
+                Err(AttemptError::Retry { error, retry_after }) => {
+                    let wait = match retry_after {
+                        Some(wait) if wait > MAX_RETRY_AFTER => return Err(format!("{} (asked to retry after {}s)", error, wait.as_secs()).into()),
+                        Some(wait) => wait,
+                        None => backoff,
+                    };
+                    println!("🔁 {}; retrying in {:.1}s ({}/{})", error, wait.as_secs_f64(), attempt, MAX_ATTEMPTS - 1);
+                    sleep(wait).await;
+                    backoff *= 2;
+                }
+            }
+        }
+        unreachable!("the last attempt always returns")
+    }
+
+    pub async fn fetch_all_players(&self) -> Result<Vec<PlayerSearchResult>, Box<dyn std::error::Error>> {
+        // Online the index is always refetched, so a build never misses newly listed players
+        if self.offline {
+            let (cache_ref, body) = self.cache.get(PLAYER_SEARCH_KEY).await.ok_or_else(|| {
+                format!("No cached player search in {}; run once without --offline to fill the cache", self.cache.dir().display())
+            })?;
+            let players: Vec<PlayerSearchResult> = serde_json::from_str(&body)?;
+            println!("📂 Using cached player search results from {} ({} players)", cache_ref.fetched_at, players.len());
+            return Ok(players);
+        }
+
+        println!("🔍 Fetching all players from NHL search API...");
+        let body = self.get(PLAYER_SEARCH_KEY, PLAYER_SEARCH_URL).await.map_err(|e| e.to_string())?;
+        let players: Vec<PlayerSearchResult> = serde_json::from_str(&body)?;
+        println!("✅ Found {} players in search results", players.len());
+        Ok(players)
+    }
+
+    /// A player's landing page, or None offline if it isn't in the cache.
+    pub async fn fetch_player_details(&self, player_id: &str) -> Result<Option<PlayerDetails>, Box<dyn std::error::Error + Send + Sync>> {
+        let key = format!("landing-{}", player_id);
+        let body = if self.offline {
+            match self.cache.get(&key).await {
+                Some((_, body)) => body,
+                None => return Ok(None),
+            }
+        } else {
+            println!("   Fetching details for player ID: {}", player_id);
+            self.get(&key, &format!("https://api-web.nhle.com/v1/player/{player_id}/landing")).await?
+        };
+        Ok(Some(serde_json::from_str(&body)?))
+    }
+}
@@ -0,0 +1,234 @@
+//! Publishing a built database as a dataset version the edge service can serve.
+//!
+//! A version is uploaded to the `journeyman` KV store through the Fastly KV API: the full
+//! dataset (in pieces if it's over the KV value limit), then its player and team entries in
+//! batches, then the manifest, so the service never sees a manifest for a half-uploaded version.
+//! A version number that's already registered is refused before anything is uploaded. The
+//! version is then registered (and optionally promoted) through the service's `/admin/datasets`
+//! endpoint, which checks the manifest itself.
+//!
+//! For local testing the same keys can be written to a JSON seed file instead, with
+//! `fastly.toml` pointed at it so Viceroy starts with the data already in place.
+
+use base64::Engine;
+use journeyman_core::dataset::{self, DatasetManifest, PlayerDatabase};
+use journeyman_core::registry::{self, DatasetRegistry, DatasetVersion, RegistryError, MAX_BLOB_BYTES, REGISTRY_KEY};
+use std::fs;
+use std::path::Path;
+
+const FASTLY_API: &str = "https://api.fastly.com";
+// The KV API takes batches as newline-delimited JSON; stay well under its request size limit
+const MAX_BATCH_ENTRIES: usize = 1000;
+const MAX_BATCH_BYTES: usize = 4 * 1024 * 1024;
+// Seed file written next to fastly.toml
+const LOCAL_SEED_FILE: &str = "kv_seed.json";
+const KV_STORE: &str = "journeyman";
+
+// A key and its value
+type KvEntry = (String, String);
+
+// Every key of a dataset version, in upload order
+struct VersionEntries {
+    // The full dataset, in pieces if it's too big for one entry
+    blob: Vec<KvEntry>,
+    // One entry per player and per team
+    split: Vec<KvEntry>,
+    manifest: DatasetManifest,
+}
+
+fn version_entries(database: &PlayerDatabase, version: u32) -> Result<VersionEntries, Box<dyn std::error::Error>> {
+    let prefix = DatasetVersion::key_prefix_for(version);
+    let mut manifest = database.manifest();
+    let blob = serde_json::to_string(database)?;
+    let blob = if blob.len() > MAX_BLOB_BYTES {
+        let pieces = registry::split_blob(&blob, MAX_BLOB_BYTES);
+        manifest.blob_chunks = pieces.len() as u32;
+        pieces.into_iter()
+            .enumerate()
+            .map(|(index, piece)| (format!("{}{}", prefix, registry::blob_chunk_key(index as u32)), piece.to_string()))
+            .collect()
+    } else {
+        vec![(DatasetVersion::blob_key_for(version), blob)]
+    };
+    let split = database.kv_entries()?
+        .into_iter()
+        .filter(|(key, _)| key != dataset::MANIFEST_KEY)
+        .map(|(key, value)| (format!("{}{}", prefix, key), value))
+        .collect();
+    Ok(VersionEntries { blob, split, manifest })
+}
+
+impl VersionEntries {
+    fn manifest_entry(&self, version: u32) -> Result<KvEntry, serde_json::Error> {
+        let key = format!("{}{}", DatasetVersion::key_prefix_for(version), dataset::MANIFEST_KEY);
+        Ok((key, serde_json::to_string(&self.manifest)?))
+    }
+}
+
+// Group entries into KV API batches by count and encoded size
+fn batches(entries: &[KvEntry]) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut batch = String::new();
+    let mut count = 0;
+    for (key, value) in entries {
+        let line = serde_json::json!({
+            "key": key,
+            "value": base64::engine::general_purpose::STANDARD.encode(value)
+        })
+        .to_string();
+        if count == MAX_BATCH_ENTRIES || (count > 0 && batch.len() + line.len() + 1 > MAX_BATCH_BYTES) {
+            batches.push(std::mem::take(&mut batch));
+            count = 0;
+        }
+        batch.push_str(&line);
+        batch.push('\n');
+        count += 1;
+    }
+    if count > 0 {
+        batches.push(batch);
+    }
+    batches
+}
+
+async fn check(response: reqwest::Response, what: &str) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    if !response.status().is_success() {
+        return Err(format!("{} failed with HTTP {}: {}", what, response.status(), response.text().await?).into());
+    }
+    Ok(response)
+}
+
+async fn put_key(client: &reqwest::Client, store_id: &str, api_token: &str, key: &str, value: String) -> Result<(), Box<dyn std::error::Error>> {
+    let response = client
+        .put(format!("{}/resources/stores/kv/{}/keys/{}", FASTLY_API, store_id, key))
+        .header("Fastly-Key", api_token)
+        .body(value)
+        .send()
+        .await?;
+    check(response, &format!("Uploading {}", key)).await?;
+    Ok(())
+}
+
+// Registry as the service has it, to pick the next version number and refuse existing ones
+async fn fetch_registry(client: &reqwest::Client, endpoint: &str, token: &str) -> Result<DatasetRegistry, Box<dyn std::error::Error>> {
+    let response = client
+        .get(format!("{}/admin/datasets", endpoint))
+        .bearer_auth(token)
+        .send()
+        .await?;
+    Ok(check(response, "Reading the dataset registry").await?.json().await?)
+}
+
+async fn admin_action(client: &reqwest::Client, endpoint: &str, token: &str, action: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+    let response = client
+        .post(format!("{}/admin/datasets", endpoint))
+        .bearer_auth(token)
+        .json(&action)
+        .send()
+        .await?;
+    check(response, &format!("{}", action["action"])).await?;
+    Ok(())
+}
+
+pub struct RemoteTarget<'a> {
+    pub store_id: &'a str,
+    pub api_token: &'a str,
+    pub endpoint: &'a str,
+    pub admin_token: &'a str,
+}
+
+pub async fn publish(database: &PlayerDatabase, version: Option<u32>, promote: bool, target: RemoteTarget<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let endpoint = target.endpoint.trim_end_matches('/');
+    // Uploading over a registered version would change it under whoever is serving it
+    let registry = fetch_registry(&client, endpoint, target.admin_token).await?;
+    let version = match version {
+        Some(version) if registry.get(version).is_some() => return Err(RegistryError::VersionExists(version).into()),
+        Some(version) => version,
+        None => registry.next_version(),
+    };
+    let entries = version_entries(database, version)?;
+    let (manifest_key, manifest) = entries.manifest_entry(version)?;
+
+    println!("📤 Publishing dataset version {} ({} players, {} keys)", version, database.players.len(),
+             entries.blob.len() + entries.split.len() + 1);
+    for (key, value) in &entries.blob {
+        put_key(&client, target.store_id, target.api_token, key, value.clone()).await?;
+    }
+
+    let batches = batches(&entries.split);
+    for (index, batch) in batches.iter().enumerate() {
+        let response = client
+            .put(format!("{}/resources/stores/kv/{}/batch", FASTLY_API, target.store_id))
+            .header("Fastly-Key", target.api_token)
+            .header("Content-Type", "application/x-ndjson")
+            .body(batch.clone())
+            .send()
+            .await?;
+        check(response, &format!("Uploading batch {} of {}", index + 1, batches.len())).await?;
+        println!("   Batch {}/{} uploaded", index + 1, batches.len());
+    }
+    put_key(&client, target.store_id, target.api_token, &manifest_key, manifest).await?;
+
+    admin_action(&client, endpoint, target.admin_token, serde_json::json!({"action": "register", "version": version})).await?;
+    println!("✅ Registered dataset version {}", version);
+    if promote {
+        admin_action(&client, endpoint, target.admin_token, serde_json::json!({"action": "promote", "version": version})).await?;
+        println!("✅ Dataset version {} is now current", version);
+    } else {
+        println!("   Promote it with: {{\"action\": \"promote\", \"version\": {}}} to {}/admin/datasets", version, endpoint);
+    }
+    Ok(())
+}
+
+// Write the version into the local seed file, registered and current, and point fastly.toml at it.
+// A version the seed already has is refused unless `force` is set, in which case it's replaced.
+pub fn publish_local(database: &PlayerDatabase, version: Option<u32>, force: bool, fastly_toml: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let seed_path = Path::new(fastly_toml).parent().unwrap_or(Path::new(".")).join(LOCAL_SEED_FILE);
+
+    // Keep whatever earlier versions the seed already has, so rollback can be tried locally
+    let mut seed: serde_json::Map<String, serde_json::Value> = match fs::read_to_string(&seed_path) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(_) => serde_json::Map::new(),
+    };
+    let mut registry: DatasetRegistry = match seed.get(REGISTRY_KEY).and_then(|value| value.as_str()) {
+        Some(registry) => serde_json::from_str(registry)?,
+        None => DatasetRegistry::default(),
+    };
+    let version = version.unwrap_or_else(|| registry.next_version());
+    if registry.get(version).is_some() {
+        if !force {
+            return Err(format!("{} in {}; pass --force to replace it", RegistryError::VersionExists(version), seed_path.display()).into());
+        }
+        // Drop every key of the old upload so none of it outlives the replacement
+        let (blob_key, prefix) = (DatasetVersion::blob_key_for(version), DatasetVersion::key_prefix_for(version));
+        seed.retain(|key, _| *key != blob_key && !key.starts_with(&prefix));
+        registry.versions.remove(&version);
+    }
+
+    let entries = version_entries(database, version)?;
+    let manifest = entries.manifest_entry(version)?;
+    let entry = DatasetVersion::new(version, &entries.manifest, chrono::Utc::now().to_rfc3339());
+    for (key, value) in entries.blob.into_iter().chain(entries.split).chain(std::iter::once(manifest)) {
+        seed.insert(key, serde_json::Value::String(value));
+    }
+    registry.register(version, entry)?;
+    if registry.current != Some(version) {
+        registry.promote(version)?;
+    }
+    seed.insert(REGISTRY_KEY.to_string(), serde_json::Value::String(serde_json::to_string(&registry)?));
+    fs::write(&seed_path, serde_json::to_string(&seed)?)?;
+
+    // Point Viceroy's KV store at the seed file, leaving the rest of fastly.toml as it was
+    let mut config: toml_edit::DocumentMut = fs::read_to_string(fastly_toml)?.parse()?;
+    let mut store = toml_edit::InlineTable::new();
+    store.insert("file", LOCAL_SEED_FILE.into());
+    store.insert("format", "json".into());
+    let local_server = config["local_server"].or_insert(toml_edit::table());
+    let kv_stores = local_server["kv_stores"].or_insert(toml_edit::table());
+    kv_stores[KV_STORE] = toml_edit::value(store);
+    fs::write(fastly_toml, config.to_string())?;
+
+    println!("✅ Wrote dataset version {} to {} and set it as current", version, seed_path.display());
+    println!("   {} now loads the {} KV store from it", fastly_toml, KV_STORE);
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! A token bucket shared by every worker, so the NHL APIs see the same request rate however many
+//! requests are in flight.
+//!
+//! The rate adapts: when the API starts refusing requests the interval doubles, and it creeps
+//! back to the configured one as requests succeed again.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+// Slowing down from no delay at all starts here
+const MIN_SLOW_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_INTERVAL: Duration = Duration::from_secs(30);
+// Each success takes this fraction off the extra delay
+const RECOVERY: f64 = 0.05;
+
+pub struct RateLimiter {
+    /// The configured time to earn one token
+    base_interval: Duration,
+    /// Most tokens that can be saved up
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Time to earn one token right now
+    interval: Duration,
+}
+
+impl RateLimiter {
+    /// One request per `interval` on average, with up to `burst` at once after a quiet spell.
+    pub fn new(interval: Duration, burst: u32) -> RateLimiter {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            base_interval: interval,
+            burst,
+            // Start with a single token so a run doesn't open with a burst
+            bucket: Mutex::new(Bucket { tokens: 1.0, refilled_at: Instant::now(), interval }),
+        }
+    }
+
+    /// Wait for a token and take it.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                if bucket.interval.is_zero() {
+                    return;
+                }
+                let now = Instant::now();
+                let earned = now.duration_since(bucket.refilled_at).as_secs_f64() / bucket.interval.as_secs_f64();
+                bucket.tokens = (bucket.tokens + earned).min(self.burst);
+                bucket.refilled_at = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                bucket.interval.mul_f64(1.0 - bucket.tokens)
+            };
+            sleep(wait).await;
+        }
+    }
+
+    /// Halve the rate, after the API pushed back. Returns the new interval.
+    pub fn slow_down(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.interval = (bucket.interval * 2).clamp(MIN_SLOW_INTERVAL, MAX_INTERVAL);
+        // Drop any saved-up burst so the slowdown takes effect now
+        bucket.tokens = bucket.tokens.min(0.0);
+        bucket.interval
+    }
+
+    /// Move back towards the configured rate, after a request succeeded.
+    pub fn speed_up(&self) {
+        let mut bucket = self.bucket.lock().unwrap();
+        if bucket.interval > self.base_interval {
+            let extra = (bucket.interval - self.base_interval).mul_f64(1.0 - RECOVERY);
+            bucket.interval = if extra < Duration::from_millis(1) { self.base_interval } else { self.base_interval + extra };
+        }
+    }
+}
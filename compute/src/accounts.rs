@@ -0,0 +1,295 @@
+//! Player accounts: registration, login and display names.
+//!
+//! KV layout:
+//! - `account_{username}`: display name, password hash and the account's user id
+//! - `user_claim_{user_id}`: which account owns a user id, anonymous or minted for the account
+//! - `user_profile_{user_id}`: public profile used to show names on the leaderboard
+//! - `login_failures_{account|ip}_{...}`: recent failed logins, to slow down password guessing
+
+use crate::auth;
+use crate::kv::Versioned;
+use fastly::http::StatusCode;
+use fastly::kv_store::{self, InsertMode, KVStoreError};
+use fastly::{mime, Error, Response};
+use journeyman_core::versioned;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+// Hashing is deliberately slow, so don't let anyone hand argon2 a megabyte
+const MAX_PASSWORD_LENGTH: usize = 128;
+const MAX_DISPLAY_NAME_LENGTH: usize = 24;
+// Failed logins allowed per account, and per client address, in each window
+const MAX_FAILED_LOGINS: u32 = 5;
+const MAX_FAILED_LOGINS_PER_IP: u32 = 20;
+const FAILED_LOGIN_WINDOW_SECS: u64 = 15 * 60;
+// Lost races on a failure counter before giving up
+const MAX_ATTEMPTS: u32 = 8;
+// Checked when there's no such account, so a missing one takes as long to refuse as a wrong password
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$UO2Q60rXxTWO3a8J4Cw6YQ$6BcuHPjZ1giOCF8p+pdgFQlVvPcvl9G1MY5S0TFTIok";
+
+pub enum AccountError {
+    InvalidUsername,
+    InvalidPassword,
+    InvalidDisplayName,
+    UsernameTaken,
+    UserIdClaimed,
+    InvalidCredentials,
+    TooManyAttempts,
+}
+
+impl AccountError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AccountError::InvalidUsername => "invalid_username",
+            AccountError::InvalidPassword => "invalid_password",
+            AccountError::InvalidDisplayName => "invalid_display_name",
+            AccountError::UsernameTaken => "username_taken",
+            AccountError::UserIdClaimed => "user_id_claimed",
+            AccountError::InvalidCredentials => "invalid_credentials",
+            AccountError::TooManyAttempts => "too_many_attempts",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AccountError::InvalidUsername => {
+                "Usernames are 3-20 letters, digits or underscores".to_string()
+            }
+            AccountError::InvalidPassword => {
+                format!("Passwords are {}-{} characters", MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH)
+            }
+            AccountError::InvalidDisplayName => {
+                format!("Display names are 1-{} characters", MAX_DISPLAY_NAME_LENGTH)
+            }
+            AccountError::UsernameTaken => "That username is already taken".to_string(),
+            AccountError::UserIdClaimed => "That play history already belongs to an account".to_string(),
+            AccountError::InvalidCredentials => "Wrong username or password".to_string(),
+            AccountError::TooManyAttempts => "Too many failed logins; try again in a few minutes".to_string(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            AccountError::UsernameTaken | AccountError::UserIdClaimed => StatusCode::CONFLICT,
+            AccountError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AccountError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    pub fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "error": self.code(),
+            "message": self.message()
+        });
+        Response::from_status(self.status())
+            .with_content_type(mime::APPLICATION_JSON)
+            .with_header("Access-Control-Allow-Origin", "*")
+            .with_body(serde_json::to_string(&body).expect("failed to serialize account error"))
+    }
+}
+
+fn account_key(username: &str) -> String {
+    format!("account_{}", username.to_lowercase())
+}
+
+fn claim_key(user_id: &str) -> String {
+    format!("user_claim_{}", user_id)
+}
+
+fn profile_key(user_id: &str) -> String {
+    format!("user_profile_{}", user_id)
+}
+
+fn account_failures_key(username: &str) -> String {
+    format!("login_failures_account_{}", username.to_lowercase())
+}
+
+fn ip_failures_key(ip: IpAddr) -> String {
+    format!("login_failures_ip_{}", ip)
+}
+
+fn valid_username(username: &str) -> bool {
+    (3..=20).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Sign a session for an account record
+fn session_response(account: &serde_json::Value) -> Result<serde_json::Value, Error> {
+    let expires_at = auth::now_secs() + auth::SESSION_TTL_SECS;
+    let token = auth::sign(&serde_json::json!({
+        "typ": "session",
+        "sub": account["username"],
+        "uid": account["user_id"],
+        "name": account["display_name"],
+        "exp": expires_at
+    }))?;
+
+    Ok(serde_json::json!({
+        "token": token,
+        "expires_at": expires_at,
+        "username": account["username"],
+        "user_id": account["user_id"],
+        "display_name": account["display_name"]
+    }))
+}
+
+// Create an account, optionally claiming the anonymous user id the client has been playing under.
+// The caller must have verified that the requester holds a signed identity for that id.
+pub fn register(
+    username: &str,
+    password: &str,
+    display_name: &str,
+    claim_user_id: Option<&str>,
+) -> Result<Result<serde_json::Value, AccountError>, Error> {
+    let display_name = display_name.trim();
+    if !valid_username(username) {
+        return Ok(Err(AccountError::InvalidUsername));
+    }
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&password.chars().count()) {
+        return Ok(Err(AccountError::InvalidPassword));
+    }
+    if display_name.is_empty() || display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+        return Ok(Err(AccountError::InvalidDisplayName));
+    }
+
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    // Claim the user id first, whether it's anonymous history or a new one; the Add mode makes
+    // the claim atomic, so no id ever ends up with two accounts
+    let user_id = match claim_user_id {
+        Some(user_id) => user_id.to_string(),
+        None => auth::random_id("acct_"),
+    };
+    let claimed = store.build_insert()
+        .mode(InsertMode::Add)
+        .execute(&claim_key(&user_id), username.to_lowercase());
+    if claimed.is_err() {
+        return Ok(Err(AccountError::UserIdClaimed));
+    }
+
+    let account = serde_json::json!({
+        "username": username,
+        "display_name": display_name,
+        "password_hash": auth::hash_password(password)?,
+        "user_id": user_id,
+        "created_at": auth::now_secs()
+    });
+    let created = store.build_insert()
+        .mode(InsertMode::Add)
+        .execute(&account_key(username), serde_json::to_string(&account)?);
+    if created.is_err() {
+        // Release the claim so the history can still be attached to another account
+        store.delete(&claim_key(&user_id))?;
+        return Ok(Err(AccountError::UsernameTaken));
+    }
+
+    let profile = serde_json::json!({
+        "username": username,
+        "display_name": display_name
+    });
+    store.insert(&profile_key(&user_id), serde_json::to_string(&profile)?)?;
+
+    Ok(Ok(session_response(&account)?))
+}
+
+// Failed logins recorded under `key` in the current window
+fn failed_logins(store: &kv_store::KVStore, key: &str) -> Result<u32, Error> {
+    let failures: serde_json::Value = match store.lookup(key) {
+        Ok(mut res) => serde_json::from_str(&res.take_body().into_string()).unwrap_or_default(),
+        Err(_) => return Ok(0),
+    };
+    let window_start = failures["window_start"].as_u64().unwrap_or(0);
+    if window_start + FAILED_LOGIN_WINDOW_SECS <= auth::now_secs() {
+        return Ok(0);
+    }
+    Ok(failures["count"].as_u64().unwrap_or(0) as u32)
+}
+
+// Count a failed login against `key`, starting a new window if the last one is over
+fn record_failed_login(store: &kv_store::KVStore, key: &str) -> Result<(), Error> {
+    let now = auth::now_secs();
+    versioned::update(&Versioned(store), key, MAX_ATTEMPTS, |current| {
+        let failures: serde_json::Value = current
+            .and_then(|body| serde_json::from_str(body).ok())
+            .unwrap_or_default();
+        let window_start = failures["window_start"].as_u64().unwrap_or(0);
+        let updated = if window_start + FAILED_LOGIN_WINDOW_SECS <= now {
+            serde_json::json!({ "window_start": now, "count": 1 })
+        } else {
+            serde_json::json!({ "window_start": window_start, "count": failures["count"].as_u64().unwrap_or(0) + 1 })
+        };
+        Ok::<_, Infallible>((updated.to_string(), ()))
+    })?.unwrap_or_else(|never| match never {});
+    Ok(())
+}
+
+pub fn login(username: &str, password: &str, client_ip: Option<IpAddr>) -> Result<Result<serde_json::Value, AccountError>, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    // Throttle guessing at one account, and one client guessing across many
+    let account_failures = account_failures_key(username);
+    let ip_failures = client_ip.map(ip_failures_key);
+    if failed_logins(&store, &account_failures)? >= MAX_FAILED_LOGINS {
+        return Ok(Err(AccountError::TooManyAttempts));
+    }
+    if let Some(key) = &ip_failures {
+        if failed_logins(&store, key)? >= MAX_FAILED_LOGINS_PER_IP {
+            return Ok(Err(AccountError::TooManyAttempts));
+        }
+    }
+
+    let account: Option<serde_json::Value> = match store.lookup(&account_key(username)) {
+        Ok(mut res) => Some(serde_json::from_str(&res.take_body().into_string())?),
+        Err(_) => None,
+    };
+    let password_hash = match &account {
+        Some(account) => account["password_hash"].as_str().unwrap_or(""),
+        None => DUMMY_PASSWORD_HASH,
+    };
+    let verified = password.chars().count() <= MAX_PASSWORD_LENGTH
+        && auth::verify_password(password, password_hash)
+        && account.is_some();
+
+    match account {
+        Some(account) if verified => {
+            match store.delete(&account_failures) {
+                Ok(()) | Err(KVStoreError::ItemNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+            Ok(Ok(session_response(&account)?))
+        }
+        _ => {
+            record_failed_login(&store, &account_failures)?;
+            if let Some(key) = &ip_failures {
+                record_failed_login(&store, key)?;
+            }
+            Ok(Err(AccountError::InvalidCredentials))
+        }
+    }
+}
+
+// Fill in display names for leaderboard entries that belong to an account
+pub fn attach_display_names(entries: &mut [serde_json::Value]) -> Result<(), Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    for entry in entries {
+        let user_id = match entry["user_id"].as_str() {
+            Some(user_id) => user_id.to_string(),
+            None => continue,
+        };
+        if let Ok(mut res) = store.lookup(&profile_key(&user_id)) {
+            let profile: serde_json::Value = serde_json::from_str(&res.take_body().into_string())?;
+            entry["display_name"] = profile["display_name"].clone();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Hand-picked daily puzzles, e.g. a themed day for the trade deadline.
+//!
+//! Curated puzzles are stored under `curated_puzzle_{day}` and win over the generated selection
+//! in `puzzle::daily_puzzle`. They're solved on the way in so they carry a difficulty like any
+//! other puzzle, and recorded in the schedule history so generated days don't repeat them.
+
+use crate::players::PlayerStore;
+use crate::puzzle::{self, Puzzle};
+use fastly::kv_store;
+use fastly::Error;
+use journeyman_core::{solver, teams};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+// One day of a curated calendar
+#[derive(Deserialize)]
+pub struct CuratedEntry {
+    pub date: String,
+    pub teams: Vec<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+pub enum CurationError {
+    InvalidDate(String),
+    PastDate(String),
+    TeamCount(usize),
+    UnknownTeam(String),
+    DuplicateTeam(String),
+    Unsolvable(String),
+}
+
+impl CurationError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CurationError::InvalidDate(_) => "invalid_date",
+            CurationError::PastDate(_) => "past_date",
+            CurationError::TeamCount(_) => "team_count",
+            CurationError::UnknownTeam(_) => "unknown_team",
+            CurationError::DuplicateTeam(_) => "duplicate_team",
+            CurationError::Unsolvable(_) => "unsolvable",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            CurationError::InvalidDate(date) => format!("'{}' is not a YYYY-MM-DD date", date),
+            CurationError::PastDate(date) => format!("{} has already started and can't be changed", date),
+            CurationError::TeamCount(count) => {
+                format!("A puzzle needs between 2 and {} teams, not {}", solver::MAX_TEAMS, count)
+            }
+            CurationError::UnknownTeam(team) => format!("'{}' is not a team name or code", team),
+            CurationError::DuplicateTeam(team) => format!("{} is listed more than once", team),
+            CurationError::Unsolvable(reason) => format!("The puzzle can't be solved: {}", reason),
+        }
+    }
+
+    pub fn to_json(&self, date: &str) -> serde_json::Value {
+        serde_json::json!({
+            "date": date,
+            "error": self.code(),
+            "message": self.message()
+        })
+    }
+}
+
+fn curate(player_store: &mut PlayerStore, entry: CuratedEntry) -> Result<Result<(u64, Puzzle), CurationError>, Error> {
+    let day = match crate::dates::day_from_iso(&entry.date) {
+        Some(day) => day,
+        None => return Ok(Err(CurationError::InvalidDate(entry.date))),
+    };
+    // Today, or any day that's already started in the first timezone to reach it, may have been played
+    if day <= crate::dates::latest_current_day() {
+        return Ok(Err(CurationError::PastDate(entry.date)));
+    }
+    if entry.teams.len() < 2 || entry.teams.len() > solver::MAX_TEAMS {
+        return Ok(Err(CurationError::TeamCount(entry.teams.len())));
+    }
+
+    let mut teams: Vec<&str> = Vec::new();
+    let mut seen = HashSet::new();
+    for team in &entry.teams {
+        let name = match teams::lookup(team) {
+            Some(team) => team.name,
+            None => return Ok(Err(CurationError::UnknownTeam(team.clone()))),
+        };
+        if !seen.insert(name) {
+            return Ok(Err(CurationError::DuplicateTeam(name.to_string())));
+        }
+        teams.push(name);
+    }
+
+    let solution = match puzzle::evaluate(player_store, &teams)? {
+        Ok(solution) => solution,
+        Err(e) => return Ok(Err(CurationError::Unsolvable(e.to_string()))),
+    };
+    Ok(Ok((day, Puzzle {
+        teams: teams.iter().map(|team| team.to_string()).collect(),
+        difficulty: solver::difficulty_label(solution.min_players, solution.optimal_covers).to_string(),
+        min_players: solution.min_players,
+        optimal_covers: solution.optimal_covers,
+        attempt: 0,
+        curated: true,
+        title: entry.title,
+    })))
+}
+
+// Store every valid entry; invalid ones are reported back without stopping the rest
+pub fn store_curated_puzzles(entries: Vec<CuratedEntry>) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+    let mut player_store = PlayerStore::open()?;
+
+    let mut stored = Vec::new();
+    let mut rejected = Vec::new();
+    for entry in entries {
+        let date = entry.date.clone();
+        match curate(&mut player_store, entry)? {
+            Ok((day, puzzle)) => {
+                store.insert(&puzzle::curated_key(day), serde_json::to_string(&puzzle)?)?;
+                puzzle::record_in_history(&store, &puzzle.teams, day)?;
+                stored.push(serde_json::json!({
+                    "date": date,
+                    "teams": puzzle.teams,
+                    "difficulty": puzzle.difficulty
+                }));
+            }
+            Err(e) => rejected.push(e.to_json(&date)),
+        }
+    }
+
+    Ok(serde_json::json!({
+        "stored": stored,
+        "rejected": rejected
+    }))
+}
@@ -0,0 +1,115 @@
+//! Past daily puzzles, numbered from launch and replayable at any time.
+//!
+//! Archive plays are scored the same way as live ones but stored under
+//! `archive_play_{user_id}_{day}`, away from the daily submissions, usage counts and leaderboard.
+
+use journeyman_core::submission::ResolvedPlayer;
+use fastly::kv_store;
+use fastly::Error;
+use std::collections::HashSet;
+
+// Puzzle #1
+const FIRST_PUZZLE_DATE: &str = "2025-09-15";
+
+pub fn first_puzzle_day() -> u64 {
+    crate::dates::day_from_iso(FIRST_PUZZLE_DATE).expect("FIRST_PUZZLE_DATE is a valid date")
+}
+
+pub fn puzzle_number(day: u64) -> u64 {
+    day.saturating_sub(first_puzzle_day()) + 1
+}
+
+// Past puzzles can be replayed once they're over in every timezone, the same bound as /solution;
+// until then someone is still playing them live
+pub fn is_archived(day: u64) -> bool {
+    day >= first_puzzle_day() && day < crate::dates::earliest_current_day()
+}
+
+fn play_prefix(user_id: &str) -> String {
+    format!("archive_play_{}_", user_id)
+}
+
+fn play_key(user_id: &str, day: u64) -> String {
+    format!("{}{}", play_prefix(user_id), day)
+}
+
+// Every past puzzle, newest first, marking the ones this user has replayed
+pub fn list_archive(user_id: Option<&str>) -> Result<serde_json::Value, Error> {
+    let mut played: HashSet<u64> = HashSet::new();
+    if let Some(user_id) = user_id {
+        let store = kv_store::KVStore::open("journeyman")
+            .expect("failed to open KV store")
+            .unwrap();
+        let prefix = play_prefix(user_id);
+        for page in store.list().prefix(&prefix).iter() {
+            played.extend(page?.keys().iter().filter_map(|key| key[prefix.len()..].parse::<u64>().ok()));
+        }
+    }
+
+    let today = crate::dates::current_day();
+    let puzzles: Vec<serde_json::Value> = (first_puzzle_day()..crate::dates::earliest_current_day())
+        .rev()
+        .map(|day| {
+            let mut entry = serde_json::json!({
+                "puzzle_number": puzzle_number(day),
+                "date": crate::dates::iso_from_day(day)
+            });
+            if user_id.is_some() {
+                entry["played"] = serde_json::json!(played.contains(&day));
+            }
+            entry
+        })
+        .collect();
+
+    Ok(serde_json::json!({
+        "today": {
+            "puzzle_number": puzzle_number(today),
+            "date": crate::dates::iso_from_day(today)
+        },
+        "puzzles": puzzles
+    }))
+}
+
+// Score and store a replay of a past puzzle (validation has already checked the day and players)
+pub fn submit_archive_play(resolved: Vec<ResolvedPlayer>, day: u64, user_id: &str) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    let key = play_key(user_id, day);
+    if store.lookup(&key).is_ok() {
+        return Ok(serde_json::json!({
+            "error": "already_submitted",
+            "message": "You have already played this puzzle"
+        }));
+    }
+
+    let puzzle = crate::puzzle::daily_puzzle(day)?;
+    let player_objects: Vec<serde_json::Value> = resolved.iter()
+        .map(|p| serde_json::json!({"name": p.name, "id": p.id}))
+        .collect();
+    let overlap_data = crate::calculate_overlap_score(&player_objects, &puzzle.teams)?;
+
+    let play = serde_json::json!({
+        "players": resolved.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(),
+        "player_ids": resolved.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(),
+        "player_count": resolved.len(),
+        "overlap_score": overlap_data["total_overlap_score"],
+        "submitted_at": std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    });
+    store.insert(&key, serde_json::to_string(&play)?)?;
+
+    Ok(serde_json::json!({
+        "success": true,
+        "puzzle_number": puzzle_number(day),
+        "date": crate::dates::iso_from_day(day),
+        "overlap_data": overlap_data,
+        "overlap_score": overlap_data["total_overlap_score"],
+        "player_count": resolved.len(),
+        "min_players": puzzle.min_players,
+        "optimal": resolved.len() == puzzle.min_players
+    }))
+}
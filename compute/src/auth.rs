@@ -0,0 +1,236 @@
+//! HMAC-signed tokens and password hashing.
+//!
+//! Tokens are `base64url(claims).base64url(hmac_sha256(claims))`, signed with a key kept in the
+//! `journeyman_secrets` secret store so it never ships with the package. Every token expires;
+//! anonymous identities are renewed by the client before they do.
+//!
+//! Browsers from before signed identities kept a self-made `user_...` id. Each can be traded in
+//! once for an identity under the same id (`legacy_identity_{user_id}` records the exchange),
+//! so its play history isn't lost.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use fastly::kv_store::{self, InsertMode};
+use fastly::secret_store::SecretStore;
+use fastly::{Error, Request};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SECRET_STORE: &str = "journeyman_secrets";
+const SIGNING_KEY_NAME: &str = "session_signing_key";
+const ADMIN_TOKEN_NAME: &str = "admin_token";
+
+// How long a login stays valid
+pub const SESSION_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+// How long an anonymous identity stays valid without being renewed
+pub const IDENTITY_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn signing_key() -> Result<Vec<u8>, Error> {
+    let store = SecretStore::open(SECRET_STORE)?;
+    let secret = store.get(SIGNING_KEY_NAME)
+        .ok_or_else(|| Error::msg(format!("secret store is missing {}", SIGNING_KEY_NAME)))?;
+    Ok(secret.plaintext().to_vec())
+}
+
+fn mac_for(key: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+// Sign a set of claims; callers are expected to include an "exp" timestamp
+pub fn sign(claims: &serde_json::Value) -> Result<String, Error> {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+    let signature = mac_for(&signing_key()?, &payload).finalize().into_bytes();
+    Ok(format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+// The claims of a correctly signed token, expired or not
+fn decode(token: &str) -> Result<Option<serde_json::Value>, Error> {
+    let (payload, signature) = match token.split_once('.') {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    let signature = match URL_SAFE_NO_PAD.decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return Ok(None),
+    };
+    if mac_for(&signing_key()?, payload).verify_slice(&signature).is_err() {
+        return Ok(None);
+    }
+
+    Ok(URL_SAFE_NO_PAD.decode(payload)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok()))
+}
+
+// Returns the claims of a correctly signed, unexpired token, or None for anything else.
+// Tokens without an "exp" are refused too.
+pub fn verify(token: &str) -> Result<Option<serde_json::Value>, Error> {
+    Ok(decode(token)?.filter(|claims| claims["exp"].as_u64().is_some_and(|exp| exp > now_secs())))
+}
+
+// Pull a bearer token out of the Authorization header
+pub fn bearer_token(req: &Request) -> Option<&str> {
+    req.get_header_str("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+}
+
+// The player a request was made by, taken from a signed token rather than anything in the body
+pub struct Player {
+    pub user_id: String,
+    pub display_name: Option<String>,
+}
+
+// A signed identity for an anonymous player id
+fn identity_for(user_id: &str) -> Result<serde_json::Value, Error> {
+    let expires_at = now_secs() + IDENTITY_TTL_SECS;
+    let token = sign(&serde_json::json!({
+        "typ": "identity",
+        "uid": user_id,
+        "iat": now_secs(),
+        "exp": expires_at
+    }))?;
+
+    Ok(serde_json::json!({
+        "user_id": user_id,
+        "token": token,
+        "expires_at": expires_at
+    }))
+}
+
+// Hand out a new anonymous player id along with a token proving the server issued it
+pub fn issue_identity() -> Result<serde_json::Value, Error> {
+    identity_for(&random_id("anon_"))
+}
+
+// A fresh token for the same id as an unexpired identity token. Like `verify`, tokens without an
+// "exp" are refused, so an identity can't be kept alive past its expiry.
+pub fn renew_identity(token: &str) -> Result<Option<serde_json::Value>, Error> {
+    let claims = match verify(token)? {
+        Some(claims) if claims["typ"].as_str() == Some("identity") => claims,
+        _ => return Ok(None),
+    };
+    match claims["uid"].as_str() {
+        Some(user_id) if !user_id.is_empty() => Ok(Some(identity_for(user_id)?)),
+        _ => Ok(None),
+    }
+}
+
+// Ids the old client made up for itself: `user_{milliseconds}_{base36}`
+fn is_legacy_id(user_id: &str) -> bool {
+    user_id.len() <= 48
+        && user_id.strip_prefix("user_").is_some_and(|rest| {
+            !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+// Trade a legacy id in for an identity under the same id. Only the first exchange of each id
+// succeeds; None means it isn't a legacy id or has already been exchanged.
+pub fn exchange_legacy_id(user_id: &str) -> Result<Option<serde_json::Value>, Error> {
+    if !is_legacy_id(user_id) {
+        return Ok(None);
+    }
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    let claimed = store.build_insert()
+        .mode(InsertMode::Add)
+        .execute(&format!("legacy_identity_{}", user_id), now_secs().to_string());
+    if claimed.is_err() {
+        return Ok(None);
+    }
+    Ok(Some(identity_for(user_id)?))
+}
+
+// The player id of an unexpired anonymous identity token. Sessions are refused: they're for
+// accounts, and only an identity proves the holder was issued that anonymous history.
+pub fn verify_identity(token: &str) -> Result<Option<String>, Error> {
+    Ok(verify(token)?
+        .filter(|claims| claims["typ"].as_str() == Some("identity"))
+        .and_then(|claims| claims["uid"].as_str().map(|user_id| user_id.to_string()))
+        .filter(|user_id| !user_id.is_empty()))
+}
+
+// Accepts an account session or an anonymous identity token; None means the request is unauthenticated
+pub fn verify_player(token: &str) -> Result<Option<Player>, Error> {
+    let claims = match verify(token)? {
+        Some(claims) => claims,
+        None => return Ok(None),
+    };
+    let user_id = match claims["uid"].as_str() {
+        Some(user_id) if !user_id.is_empty() => user_id.to_string(),
+        _ => return Ok(None),
+    };
+
+    Ok(match claims["typ"].as_str() {
+        Some("session") => Some(Player {
+            user_id,
+            display_name: claims["name"].as_str().map(|name| name.to_string()),
+        }),
+        Some("identity") => Some(Player { user_id, display_name: None }),
+        _ => None,
+    })
+}
+
+pub fn authenticate(req: &Request) -> Result<Option<Player>, Error> {
+    match bearer_token(req) {
+        Some(token) => verify_player(token),
+        None => Ok(None),
+    }
+}
+
+// True if the request carries the admin token from the secret store
+pub fn is_admin(req: &Request) -> Result<bool, Error> {
+    let provided = match bearer_token(req) {
+        Some(token) => token,
+        None => return Ok(false),
+    };
+    let store = SecretStore::open(SECRET_STORE)?;
+    let expected = match store.get(ADMIN_TOKEN_NAME) {
+        Some(secret) => String::from_utf8_lossy(&secret.plaintext()).into_owned(),
+        None => return Ok(false),
+    };
+
+    // Compare MACs of the two tokens so the check takes the same time however much of it matches
+    let key = signing_key()?;
+    let expected_mac = mac_for(&key, &expected).finalize().into_bytes();
+    Ok(mac_for(&key, provided).verify_slice(&expected_mac).is_ok())
+}
+
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| Error::msg(format!("failed to hash password: {}", e)))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+// Random hex string for ids the server hands out
+pub fn random_id(prefix: &str) -> String {
+    let mut bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", prefix, hex)
+}
@@ -0,0 +1,101 @@
+//! Puzzle days and the calendar dates players see.
+//!
+//! Internally a puzzle is identified by its day number (days since 1970-01-01), which is what the
+//! KV keys use. The day rolls over at midnight in the configured `rollover_timezone` (UTC unless
+//! set), or in the client's own timezone when it sends one. Responses always use YYYY-MM-DD.
+
+use chrono::{DateTime, Days, NaiveDate, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use fastly::config_store::ConfigStore;
+
+const CONFIG_STORE: &str = "journeyman_config";
+
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).expect("the epoch is a valid date")
+}
+
+fn now() -> DateTime<Utc> {
+    DateTime::from(std::time::SystemTime::now())
+}
+
+// Where the puzzle changes at midnight for clients that don't say otherwise
+pub fn rollover_timezone() -> Tz {
+    ConfigStore::try_open(CONFIG_STORE)
+        .ok()
+        .and_then(|store| store.get("rollover_timezone"))
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+// The client's IANA timezone if it sent one, otherwise the rollover timezone
+pub fn resolve_timezone(client: Option<&str>) -> Result<Tz, String> {
+    match client {
+        Some(name) => name.parse().map_err(|_| format!("'{}' is not an IANA timezone", name)),
+        None => Ok(rollover_timezone()),
+    }
+}
+
+pub fn day_from_date(date: NaiveDate) -> Option<u64> {
+    u64::try_from(date.signed_duration_since(epoch()).num_days()).ok()
+}
+
+// Today's puzzle day for someone in `tz`
+pub fn current_day_in(tz: Tz) -> u64 {
+    day_from_date(now().with_timezone(&tz).date_naive()).expect("the clock is after 1970")
+}
+
+// Today's puzzle day in the rollover timezone
+pub fn current_day() -> u64 {
+    current_day_in(rollover_timezone())
+}
+
+// Days before this one have finished in every timezone
+pub fn earliest_current_day() -> u64 {
+    current_day_in(Tz::Etc__GMTPlus12)
+}
+
+// The newest day that has started anywhere (UTC+14); days after it haven't been played at all
+pub fn latest_current_day() -> u64 {
+    current_day_in(Tz::Etc__GMTMinus14)
+}
+
+// Day number for a YYYY-MM-DD date, or None if it isn't one
+pub fn day_from_iso(date: &str) -> Option<u64> {
+    day_from_date(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?)
+}
+
+// The calendar date of a day number, or None past the last date chrono can represent
+fn date_from_day(day: u64) -> Option<NaiveDate> {
+    epoch().checked_add_days(Days::new(day))
+}
+
+// YYYY-MM-DD for a day number. Days come from `parse_date` or the clock, so they always have a
+// date; the bare number is only a fallback that keeps a bad one from panicking.
+pub fn iso_from_day(day: u64) -> String {
+    match date_from_day(day) {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => day.to_string(),
+    }
+}
+
+// A date from a client: YYYY-MM-DD, or a bare day number from clients that predate ISO dates.
+// Day numbers with no calendar date are rejected like any other malformed date.
+pub fn parse_date(date: &str) -> Option<u64> {
+    day_from_iso(date).or_else(|| date.parse().ok().filter(|day| date_from_day(*day).is_some()))
+}
+
+// When the next puzzle starts in `tz`, as an RFC 3339 timestamp in that timezone
+pub fn next_puzzle_at(tz: Tz) -> String {
+    let tomorrow = now().with_timezone(&tz).date_naive() + Days::new(1);
+    // Midnight can fall in a DST gap in a few timezones, in which case the day starts at 1am
+    let start = tz.from_local_datetime(&tomorrow.and_hms_opt(0, 0, 0).expect("midnight is a valid time"))
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&tomorrow.and_hms_opt(1, 0, 0).expect("1am is a valid time")).earliest())
+        .expect("every day has a 1am");
+    start.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// The current time as an RFC 3339 timestamp
+pub fn now_rfc3339() -> String {
+    now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
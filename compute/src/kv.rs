@@ -0,0 +1,33 @@
+//! The KV store as a `VersionedStore`, for the shared generation-checked updates.
+
+use fastly::kv_store::{InsertMode, KVStore, KVStoreError};
+use journeyman_core::versioned::VersionedStore;
+
+pub struct Versioned<'a>(pub &'a KVStore);
+
+impl VersionedStore for Versioned<'_> {
+    type Error = KVStoreError;
+
+    fn read(&self, key: &str) -> Result<Option<(String, u64)>, KVStoreError> {
+        match self.0.lookup(key) {
+            Ok(mut res) => {
+                let generation = res.current_generation();
+                Ok(Some((res.take_body().into_string(), generation)))
+            }
+            Err(KVStoreError::ItemNotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_if(&self, key: &str, value: &str, generation: Option<u64>) -> Result<bool, KVStoreError> {
+        let insert = match generation {
+            Some(generation) => self.0.build_insert().if_generation_match(generation),
+            None => self.0.build_insert().mode(InsertMode::Add),
+        };
+        match insert.execute(key, value) {
+            Ok(()) => Ok(true),
+            Err(KVStoreError::ItemPreconditionFailed) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Daily leaderboard.
+//!
+//! Each user's submission is stored under `daily_submission_{date}_{user_id}`, and the day's
+//! board is kept alongside as one `daily_leaderboard_{date}` entry: every submission's score,
+//! best first. Submitting adds to it with a generation-checked write, so reading the board is a
+//! single lookup however many people have played. Days from before the board was kept are
+//! read from the individual submissions instead.
+//!
+//! How entries are ordered, ranked and shown is up to `journeyman_core::ranking`.
+
+use crate::kv::Versioned;
+use crate::{accounts, archive, dates};
+use fastly::kv_store;
+use fastly::Error;
+use journeyman_core::{ranking, usage, versioned};
+use std::collections::HashMap;
+
+// Everyone who submits at once writes the same board, so allow for plenty of lost races
+const MAX_ATTEMPTS: u32 = 16;
+
+pub fn submission_key(date: &str, user_id: &str) -> String {
+    format!("{}{}", submission_prefix(date), user_id)
+}
+
+fn submission_prefix(date: &str) -> String {
+    format!("daily_submission_{}_", date)
+}
+
+fn leaderboard_key(date: &str) -> String {
+    format!("daily_leaderboard_{}", date)
+}
+
+// The board rebuilt from every individual submission, for days it wasn't kept
+fn listed_entries(store: &kv_store::KVStore, date: &str) -> Result<Vec<serde_json::Value>, Error> {
+    let prefix = submission_prefix(date);
+    let mut entries = Vec::new();
+
+    for page in store.list().prefix(&prefix).iter() {
+        for key in page?.keys() {
+            // A key can disappear between listing and lookup; just skip it
+            let mut res = match store.lookup(key) {
+                Ok(res) => res,
+                Err(_) => continue,
+            };
+            let body = res.take_body().into_string();
+            let submission: serde_json::Value = match serde_json::from_str(&body) {
+                Ok(s) => s,
+                Err(_) => {
+                    println!("Skipping unreadable submission {}", key);
+                    continue;
+                }
+            };
+            entries.push(ranking::board_entry(&key[prefix.len()..], &submission));
+        }
+    }
+
+    ranking::sort_entries(&mut entries);
+    Ok(entries)
+}
+
+// The day's board as stored, best first
+fn stored_entries(store: &kv_store::KVStore, date: &str) -> Result<Vec<serde_json::Value>, Error> {
+    match store.lookup(&leaderboard_key(date)) {
+        Ok(mut res) => Ok(serde_json::from_str(&res.take_body().into_string())?),
+        Err(kv_store::KVStoreError::ItemNotFound) => listed_entries(store, date),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Number of submissions for a day
+pub fn count_submissions(store: &kv_store::KVStore, date: &str) -> Result<usize, Error> {
+    Ok(stored_entries(store, date)?.len())
+}
+
+// Rank a day's entries (see `journeyman_core::ranking`) and date the board
+fn ranked(day: u64, mut submissions: Vec<serde_json::Value>, usage_stats: &HashMap<String, u32>) -> serde_json::Value {
+    ranking::rank(&mut submissions, usage_stats);
+    serde_json::json!({
+        "date": dates::iso_from_day(day),
+        "puzzle_number": archive::puzzle_number(day),
+        "submissions": submissions
+    })
+}
+
+// Get daily leaderboard, ranked best first
+pub fn get_daily_leaderboard(day: u64) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    let date = day.to_string();
+    let entries = stored_entries(&store, &date)?;
+    let usage_stats = usage::get_usage(&Versioned(&store), &date)?;
+    Ok(ranked(day, entries, &usage_stats))
+}
+
+// Add a stored submission to the day's board and return the board as written
+pub fn record_submission(
+    store: &kv_store::KVStore,
+    day: u64,
+    user_id: &str,
+    submission: &serde_json::Value,
+    usage_stats: &HashMap<String, u32>,
+) -> Result<serde_json::Value, Error> {
+    let date = day.to_string();
+    // Only needed if this is the first write of the board for a day that already has submissions
+    let mut listed: Option<Vec<serde_json::Value>> = None;
+
+    let entries = versioned::update(&Versioned(store), &leaderboard_key(&date), MAX_ATTEMPTS, |current| {
+        let mut entries: Vec<serde_json::Value> = match current.and_then(|body| serde_json::from_str(body).ok()) {
+            Some(entries) => entries,
+            None => match &listed {
+                Some(entries) => entries.clone(),
+                None => listed.insert(listed_entries(store, &date)?).clone(),
+            },
+        };
+        // The listing may already include this submission
+        entries.retain(|entry| entry["user_id"].as_str() != Some(user_id));
+        entries.push(ranking::board_entry(user_id, submission));
+        ranking::sort_entries(&mut entries);
+        Ok::<_, Error>((serde_json::to_string(&entries)?, entries))
+    })??;
+
+    Ok(ranked(day, entries, usage_stats))
+}
+
+// Where a submission ranks on a day's ranked board
+pub fn calculate_leaderboard_position(submission: &serde_json::Value, leaderboard: &serde_json::Value) -> u32 {
+    ranking::position(submission, leaderboard["submissions"].as_array().map_or(&[], Vec::as_slice))
+}
+
+// Build the top-N and "around me" views that /get_leaderboard returns, with account display names
+pub fn leaderboard_view(leaderboard: &serde_json::Value, top_n: usize, user_id: Option<&str>) -> Result<serde_json::Value, Error> {
+    ranking::view(leaderboard, top_n, user_id, accounts::attach_display_names)
+}
@@ -4,121 +4,80 @@ use fastly::http::header::LAST_MODIFIED;
 use fastly::http::{header, Method, StatusCode};
 use fastly::kv_store;
 use fastly::{mime, Error, Request, Response};
-use std::collections::HashMap;
-
-fn get(version: u32) -> Result<serde_json::Value, Error> {
+use chrono_tz::Tz;
+use journeyman_core::dataset::{PlayerDatabase, PlayerEntry};
+use journeyman_core::ranking;
+use journeyman_core::submission::ResolvedPlayer;
+use journeyman_core::teams;
+use journeyman_core::usage;
+use journeyman_core::versioned::VersionedStore;
+
+mod accounts;
+mod admin;
+mod archive;
+mod auth;
+mod dates;
+mod kv;
+mod leaderboard;
+mod players;
+mod puzzle;
+mod registry;
+mod solution;
+mod validation;
+
+use kv::Versioned;
+use leaderboard::{calculate_leaderboard_position, get_daily_leaderboard};
+use players::PlayerStore;
+
+// A registered dataset version, or the current one; None if that version isn't registered
+fn get(version: Option<u32>) -> Result<Option<PlayerDatabase>, Error> {
     let store = kv_store::KVStore::open("journeyman")
         .expect("failed to open KV store")
         .unwrap();
-    let mut res = if version == 1 {
-        store.lookup("players")
-    } else if version == 2 {
-        store.lookup("playersv2")
-    } else {
-        unimplemented!()
-        // store.lookup("unimplemented")
-    }?;
-    let body = res.take_body();
-    let json: serde_json::Value =
-        serde_json::from_str(&body.into_string()).expect("json deserialization failed");
-    Ok(json)
+    let version = match version {
+        Some(version) => match registry::load_registry(&store)?.get(version) {
+            Some(entry) => entry.clone(),
+            None => return Ok(None),
+        },
+        None => registry::current(&store)?,
+    };
+    Ok(Some(registry::load(&store, &version)?))
 }
 
-// Generate deterministic daily teams based on current date
-fn get_daily_teams() -> Result<serde_json::Value, Error> {
-    // Get current date as days since epoch for deterministic seed
-    let now = std::time::SystemTime::now();
-    let epoch = std::time::UNIX_EPOCH;
-    let duration = now.duration_since(epoch).unwrap();
-    let days_since_epoch = duration.as_secs() / (24 * 60 * 60);
-    
-    // All team names
-    let all_teams = vec![
-        "Anaheim Ducks", "Boston Bruins", "Buffalo Sabres", "Calgary Flames",
-        "Carolina Hurricanes", "Chicago Blackhawks", "Colorado Avalanche", 
-        "Columbus Blue Jackets", "Dallas Stars", "Detroit Red Wings",
-        "Edmonton Oilers", "Florida Panthers", "Los Angeles Kings", 
-        "Minnesota Wild", "Montreal Canadiens", "Nashville Predators",
-        "New Jersey Devils", "New York Islanders", "New York Rangers", 
-        "Ottawa Senators", "Philadelphia Flyers", "Pittsburgh Penguins",
-        "San Jose Sharks", "Seattle Kraken", "St. Louis Blues", 
-        "Tampa Bay Lightning", "Toronto Maple Leafs", "Utah Hockey Club",
-        "Vancouver Canucks", "Vegas Golden Knights", "Washington Capitals", 
-        "Winnipeg Jets"
-    ];
-    
-    // Simple deterministic selection using day as seed
-    let mut selected_teams = Vec::new();
-    let mut seed = days_since_epoch as usize;
-    
-    // Select 8 teams deterministically
-    let mut available_teams = all_teams.clone();
-    for _ in 0..8 {
-        let index = seed % available_teams.len();
-        selected_teams.push(available_teams.remove(index));
-        seed = (seed * 1103515245 + 12345) % (1 << 31); // Simple LCG
-    }
+// The whole current dataset, parsed into the shared schema. Prefer `PlayerStore` where the
+// players are known by id.
+fn load_dataset() -> Result<PlayerDatabase, Error> {
+    get(None)?.ok_or_else(|| Error::msg("no current dataset"))
+}
+
+// The teams for a day's puzzle; today's in `tz` unless an archived day is asked for
+fn get_daily_teams(days_since_epoch: u64, tz: Tz) -> Result<serde_json::Value, Error> {
+    let puzzle = puzzle::daily_puzzle(days_since_epoch)?;
     
     let response = serde_json::json!({
-        "teams": selected_teams,
-        "date": format!("{}", days_since_epoch),
-        "generated_at": format!("{:?}", now)
+        "teams": puzzle.teams,
+        "difficulty": puzzle.difficulty,
+        "title": puzzle.title,
+        "date": dates::iso_from_day(days_since_epoch),
+        "puzzle_number": archive::puzzle_number(days_since_epoch),
+        "archive": days_since_epoch != dates::current_day_in(tz),
+        "timezone": tz.name(),
+        "next_puzzle_at": dates::next_puzzle_at(tz),
+        "generated_at": dates::now_rfc3339()
     });
     
     Ok(response)
 }
 
 fn get_teams_played_for(player_id: &str) -> Result<Vec<String>, Error> {
-    let player_data = get(2)?;
-    let mut teams = Vec::new();
-    
-    if let Some(teams_obj) = player_data["teams"].as_object() {
-        for (team_code, team_players) in teams_obj {
-            if let Some(players_array) = team_players.as_array() {
-                // Look for player in the new PlayerInfo format
-                let team_has_player = players_array.iter()
-                    .any(|p| {
-                        if let Some(db_player_obj) = p.as_object() {
-                            // Try to match by ID first (most reliable), then fall back to name
-                            if let Some(db_id) = db_player_obj.get("id").and_then(|id| id.as_str()) {
-                                player_id == db_id
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    });
-                
-                if team_has_player {
-                    teams.push(team_code.to_string());
-                }
-            }
-        }
-    }
-  
-    Ok(teams)
+    Ok(PlayerStore::open()?.player(player_id)?.map(|p| p.teams).unwrap_or_default())
 }
 
 // Calculate overlap score based on actual player usage in daily submissions
 fn calculate_overlap_score(players: &[serde_json::Value], game_teams: &[String]) -> Result<serde_json::Value, Error> {
-    // Get the full player database to calculate team specialization
-    let player_data = get(2)?;
-    
-    // Team code mapping
-    let team_codes: HashMap<&str, &str> = [
-        ("Anaheim Ducks", "ANA"), ("Boston Bruins", "BOS"), ("Buffalo Sabres", "BUF"),
-        ("Calgary Flames", "CGY"), ("Carolina Hurricanes", "CAR"), ("Chicago Blackhawks", "CHI"),
-        ("Colorado Avalanche", "COL"), ("Columbus Blue Jackets", "CBJ"), ("Dallas Stars", "DAL"),
-        ("Detroit Red Wings", "DET"), ("Edmonton Oilers", "EDM"), ("Florida Panthers", "FLA"),
-        ("Los Angeles Kings", "LAK"), ("Minnesota Wild", "MIN"), ("Montreal Canadiens", "MTL"),
-        ("Nashville Predators", "NSH"), ("New Jersey Devils", "NJD"), ("New York Islanders", "NYI"),
-        ("New York Rangers", "NYR"), ("Ottawa Senators", "OTT"), ("Philadelphia Flyers", "PHI"),
-        ("Pittsburgh Penguins", "PIT"), ("San Jose Sharks", "SJS"), ("Seattle Kraken", "SEA"),
-        ("St. Louis Blues", "STL"), ("Tampa Bay Lightning", "TBL"), ("Toronto Maple Leafs", "TOR"),
-        ("Utah Hockey Club", "UTA"), ("Vancouver Canucks", "VAN"), ("Vegas Golden Knights", "VGK"),
-        ("Washington Capitals", "WSH"), ("Winnipeg Jets", "WPG")
-    ].iter().cloned().collect();
+    let player_store = PlayerStore::open()?;
+    // Only loaded if a player comes without an id
+    let mut player_data: Option<PlayerDatabase> = None;
     
     let mut total_overlap_score = 0.0;
     let mut player_scores = Vec::new();
@@ -136,57 +95,25 @@ fn calculate_overlap_score(players: &[serde_json::Value], game_teams: &[String])
         
         let player_id = player_obj.get("id").and_then(|id| id.as_str());
         
-        // Find how many total teams this player played for
-        let mut total_teams_played = 0;
-        let mut teams_in_current_game = 0;
-        let mut player_info = None;
-        
-        if let Some(teams_obj) = player_data["teams"].as_object() {
-            for (team_code, team_players) in teams_obj {
-                if let Some(players_array) = team_players.as_array() {
-                    // Look for player in the new PlayerInfo format
-                    let team_has_player = players_array.iter()
-                        .any(|p| {
-                            if let Some(db_player_obj) = p.as_object() {
-                                // Try to match by ID first (most reliable), then fall back to name
-                                if let (Some(player_id), Some(db_id)) = (player_id, db_player_obj.get("id").and_then(|id| id.as_str())) {
-                                    let matches = player_id == db_id;
-                                    if matches && player_info.is_none() {
-                                        player_info = Some(p.clone());
-                                    }
-                                    matches
-                                } else if let Some(db_player_name) = db_player_obj.get("name").and_then(|n| n.as_str()) {
-                                    let matches = db_player_name.eq_ignore_ascii_case(player_name);
-                                    if matches && player_info.is_none() {
-                                        player_info = Some(p.clone());
-                                    }
-                                    matches
-                                } else {
-                                    false
-                                }
-                            } else {
-                                // Fallback for old string format
-                                p.as_str().unwrap_or("").eq_ignore_ascii_case(player_name)
-                            }
-                        });
-                    
-                    if team_has_player {
-                        total_teams_played += 1;
-                        
-                        // Check if this team is in the current game
-                        let team_name = team_codes.iter()
-                            .find(|(_, code)| *code == team_code)
-                            .map(|(name, _)| *name);
-                        
-                        if let Some(name) = team_name {
-                            if game_teams.contains(&name.to_string()) {
-                                teams_in_current_game += 1;
-                            }
-                        }
-                    }
+        // Match by ID where we have one (most reliable), otherwise by name
+        let player_entry = match player_id {
+            Some(player_id) => player_store.player(player_id)?,
+            None => {
+                if player_data.is_none() {
+                    player_data = Some(load_dataset()?);
                 }
+                player_data.as_ref()
+                    .and_then(|data| data.player_by_name(player_name).and_then(|p| data.player_entry(&p.id)))
             }
-        }
+        };
+        let played_for = player_entry.as_ref().map(|p| p.teams.as_slice()).unwrap_or_default();
+
+        // Find how many total teams this player played for, and how many are in the current game
+        let total_teams_played = played_for.len();
+        let teams_in_current_game = played_for.iter()
+            .filter_map(|code| teams::by_code(code))
+            .filter(|team| game_teams.iter().any(|name| name == team.name))
+            .count();
         
         // Calculate specialization score: higher score for players who played for more teams
         // in the current game, regardless of how many other teams they played for
@@ -212,8 +139,8 @@ fn calculate_overlap_score(players: &[serde_json::Value], game_teams: &[String])
         });
         
         // Include player info if found
-        if let Some(info) = player_info {
-            player_score["player_info"] = info;
+        if let Some(entry) = player_entry {
+            player_score["player_info"] = serde_json::to_value(entry.info)?;
         }
         
         player_scores.push(player_score);
@@ -228,95 +155,138 @@ fn calculate_overlap_score(players: &[serde_json::Value], game_teams: &[String])
 }
 
 // Submit a daily solution and update usage statistics
-fn submit_daily_solution(players: Vec<String>, date: String, user_id: String) -> Result<serde_json::Value, Error> {
+fn submit_daily_solution(resolved: Vec<ResolvedPlayer>, day: u64, user_id: String, display_name: Option<String>) -> Result<serde_json::Value, Error> {
     let store = kv_store::KVStore::open("journeyman")
         .expect("failed to open KV store")
         .unwrap();
+    let date = day.to_string();
     
-    // Check if user already submitted for this date
-    let submission_key = format!("daily_submission_{}_{}", date, user_id);
-    if store.lookup(&submission_key).is_ok() {
-        return Ok(serde_json::json!({
-            "error": "already_submitted",
-            "message": "You have already submitted a solution for today"
-        }));
-    }
+    let submission_key = leaderboard::submission_key(&date, &user_id);
     
-    // Get daily teams for this date
-    let daily_teams_data = get_daily_teams()?;
-    let daily_teams: Vec<String> = daily_teams_data["teams"].as_array()
-        .unwrap_or(&vec![])
-        .iter()
-        .map(|v| v.as_str().unwrap_or("").to_string())
-        .collect();
+    // Get daily teams for this date (validation has already confirmed it is today's puzzle)
+    let daily_teams = puzzle::daily_puzzle(day)?.teams;
     
     // Calculate current overlap score
-    let player_objects: Vec<serde_json::Value> = players.iter()
-        .map(|name| serde_json::json!({"name": name, "id": null}))
+    let player_objects: Vec<serde_json::Value> = resolved.iter()
+        .map(|p| serde_json::json!({"name": p.name, "id": p.id}))
         .collect();
+    let players: Vec<String> = resolved.iter().map(|p| p.name.clone()).collect();
     let overlap_data = calculate_overlap_score(&player_objects, &daily_teams)?;
     
-    // Update player usage statistics
-    let usage_key = format!("daily_usage_{}", date);
-    let mut usage_stats: HashMap<String, u32> = match store.lookup(&usage_key) {
-        Ok(mut res) => {
-            let body = res.take_body();
-            serde_json::from_str(&body.into_string()).unwrap_or_else(|_| HashMap::new())
-        },
-        Err(_) => HashMap::new()
-    };
-    
-    // Increment usage count for each player
-    for player in &players {
-        *usage_stats.entry(player.clone()).or_insert(0) += 1;
-    }
-    
-    // Save updated usage statistics
-    let usage_json = serde_json::to_string(&usage_stats).unwrap();
-    store.insert(&usage_key, usage_json.as_bytes())?;
-    
-    // Save user's submission
     let submission_data = serde_json::json!({
         "players": players,
+        "player_ids": resolved.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(),
         "player_count": players.len(),
         "overlap_score": overlap_data["total_overlap_score"],
+        "display_name": display_name,
         "submitted_at": std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs()
     });
     let submission_json = serde_json::to_string(&submission_data).unwrap();
-    store.insert(&submission_key, submission_json.as_bytes())?;
+
+    // Claim the submission before counting its picks: the write only succeeds if the key doesn't
+    // exist yet, so two requests racing for the same user can't both be counted
+    let versioned = Versioned(&store);
+    if !versioned.write_if(&submission_key, &submission_json, None)? {
+        return Ok(serde_json::json!({
+            "error": "already_submitted",
+            "message": "You have already submitted a solution for today"
+        }));
+    }
     
-    // Get leaderboard position
-    let leaderboard = get_daily_leaderboard(&date)?;
+    // Update player usage statistics. Counts are by id, so namesakes aren't lumped together.
+    let player_ids: Vec<String> = resolved.iter().map(|p| p.id.clone()).collect();
+    let usage_stats = usage::record_usage(&versioned, &date, &player_ids)?;
     
+    // Add the submission to the day's leaderboard
+    let leaderboard = leaderboard::record_submission(&store, day, &user_id, &submission_data, &usage_stats)?;
+    let total_submissions = leaderboard["submissions"].as_array().map(|s| s.len()).unwrap_or(0);
+
+    let percentages = usage::pick_percentages(&usage_stats, total_submissions);
+    let player_rarity: serde_json::Map<String, serde_json::Value> = player_ids.iter()
+        .map(|id| (id.clone(), serde_json::json!(percentages.get(id).copied().unwrap_or(0.0))))
+        .collect();
+
     Ok(serde_json::json!({
         "success": true,
+        "date": dates::iso_from_day(day),
+        "puzzle_number": archive::puzzle_number(day),
         "overlap_data": overlap_data,
+        "overlap_score": overlap_data["total_overlap_score"],
+        "rarity_score": usage::rarity_score(&player_ids, &percentages),
+        "pick_percentages": player_rarity,
         "leaderboard_position": calculate_leaderboard_position(&submission_data, &leaderboard),
-        "total_submissions": leaderboard["submissions"].as_array().unwrap_or(&vec![]).len()
+        "total_submissions": total_submissions
     }))
 }
 
-// Get daily leaderboard
-fn get_daily_leaderboard(date: &str) -> Result<serde_json::Value, Error> {
-    let _store = kv_store::KVStore::open("journeyman")
+// Report whether a user has already played a given day, and what they submitted
+fn get_daily_status(day: u64, user_id: &str) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
         .expect("failed to open KV store")
         .unwrap();
-    
-    // This would typically scan all submissions for the date
-    // For now, return a placeholder structure
+
+    let submission_key = leaderboard::submission_key(&day.to_string(), user_id);
+    let submission: serde_json::Value = match store.lookup(&submission_key) {
+        Ok(mut res) => serde_json::from_str(&res.take_body().into_string())?,
+        Err(_) => {
+            return Ok(serde_json::json!({
+                "date": dates::iso_from_day(day),
+                "puzzle_number": archive::puzzle_number(day),
+                "played": false
+            }))
+        }
+    };
+
+    let leaderboard = get_daily_leaderboard(day)?;
+
     Ok(serde_json::json!({
-        "date": date,
-        "submissions": []
+        "date": dates::iso_from_day(day),
+        "puzzle_number": archive::puzzle_number(day),
+        "played": true,
+        "submission": submission,
+        "score": {
+            "player_count": submission["player_count"],
+            "overlap_score": submission["overlap_score"]
+        },
+        "leaderboard_position": calculate_leaderboard_position(&submission, &leaderboard)
     }))
 }
 
-// Calculate where this submission ranks
-fn calculate_leaderboard_position(_submission: &serde_json::Value, _leaderboard: &serde_json::Value) -> u32 {
-    // Placeholder - would compare player_count first, then overlap_score
-    1
+// How often each player has been picked for a day, most popular first
+fn get_daily_usage(day: u64) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    let date = day.to_string();
+    let usage_stats = usage::get_usage(&Versioned(&store), &date)?;
+    let total_submissions = leaderboard::count_submissions(&store, &date)?;
+    let percentages = usage::pick_percentages(&usage_stats, total_submissions);
+
+    let mut players: Vec<(&String, &u32)> = usage_stats.iter().collect();
+    players.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let player_store = PlayerStore::open()?;
+    let mut entries = Vec::new();
+    for (id, picks) in players {
+        let name = player_store.player(id)?.map(|entry| entry.info.name);
+        entries.push(serde_json::json!({
+            "id": id,
+            "name": name,
+            "picks": picks,
+            "pick_percentage": percentages[id]
+        }));
+    }
+
+    Ok(serde_json::json!({
+        "date": dates::iso_from_day(day),
+        "puzzle_number": archive::puzzle_number(day),
+        "total_submissions": total_submissions,
+        "players": entries
+    }))
 }
 
 /// The entry point for your application.
@@ -337,7 +307,8 @@ fn main(req: Request) -> Result<Response, Error> {
 	if req.get_method() == Method::OPTIONS {
         return Ok(Response::from_status(StatusCode::OK)
 			.with_header("Access-Control-Allow-Origin","*")
-			.with_header("Access-Control-Allow-Headers","*")
+			// A wildcard never covers Authorization, so list the headers clients send
+			.with_header("Access-Control-Allow-Headers","Authorization, Content-Type")
 			.with_header("Vary","Origin")
             .with_body_text_plain(""))
 	}    
@@ -358,28 +329,196 @@ fn main(req: Request) -> Result<Response, Error> {
     // Pattern match on the path...
     match req.get_path() {
         "/get_players" => {
-            let db = get(1)?;
-            // Example of returning a JSON response.
+            // Older clients get the shape they were written against
+            let db = load_dataset()?.to_legacy();
             Ok(Response::from_status(StatusCode::OK)
                 .with_content_type(mime::APPLICATION_JSON)
                 .with_header("Access-Control-Allow-Origin", "*")
                 .with_body(serde_json::to_string(&db).expect("failed to serialize DB")))
         },
         "/get_playersv2" => {
-            let db = get(2)?;
-            // Example of returning a JSON response.
+            let db = load_dataset()?.to_v2();
             Ok(Response::from_status(StatusCode::OK)
                 .with_content_type(mime::APPLICATION_JSON)
                 .with_header("Access-Control-Allow-Origin", "*")
                 .with_body(serde_json::to_string(&db).expect("failed to serialize DB")))
         },
+        "/get_dataset" => {
+            // ?version=N for a registered version other than the current one
+            let version = match req.get_query_parameter("version").map(|v| v.parse::<u32>()) {
+                Some(Ok(version)) => Some(version),
+                Some(Err(_)) => return Ok(bad_request("version must be a number")),
+                None => None,
+            };
+            match get(version)? {
+                Some(db) => Ok(Response::from_status(StatusCode::OK)
+                    .with_content_type(mime::APPLICATION_JSON)
+                    .with_header("Access-Control-Allow-Origin", "*")
+                    .with_body(serde_json::to_string(&db).expect("failed to serialize DB"))),
+                None => Ok(Response::from_status(StatusCode::NOT_FOUND)
+                    .with_header("Access-Control-Allow-Origin", "*")
+                    .with_body_text_plain("No such dataset version\n")),
+            }
+        },
         "/get_daily_teams" => {
-            let daily_teams = get_daily_teams()?;
+            // ?date=YYYY-MM-DD replays a past puzzle; future puzzles stay secret
+            let tz = match request_timezone(&req) {
+                Ok(tz) => tz,
+                Err(response) => return Ok(response),
+            };
+            let day = match request_day(&req) {
+                Ok(day) if archive::is_archived(day) || day == dates::current_day_in(tz) => day,
+                Ok(_) => {
+                    return Ok(Response::from_status(StatusCode::NOT_FOUND)
+                        .with_header("Access-Control-Allow-Origin", "*")
+                        .with_body_text_plain("No puzzle is available for that date"))
+                }
+                Err(response) => return Ok(response),
+            };
+
+            let daily_teams = get_daily_teams(day, tz)?;
             Ok(Response::from_status(StatusCode::OK)
                 .with_content_type(mime::APPLICATION_JSON)
                 .with_header("Access-Control-Allow-Origin", "*")
                 .with_body(serde_json::to_string(&daily_teams).expect("failed to serialize daily teams")))
         },
+        "/get_leaderboard" => {
+            // Default to today's puzzle when no date is given
+            let day = match request_day(&req) {
+                Ok(day) => day,
+                Err(response) => return Ok(response),
+            };
+            let limit = req.get_query_parameter("limit")
+                .and_then(|l| l.parse::<usize>().ok())
+                .unwrap_or(ranking::DEFAULT_TOP_N);
+            // The "me" and "around me" views are only for a signed-in player's own entry
+            let player = auth::authenticate(&req)?;
+
+            let board = get_daily_leaderboard(day)?;
+            let view = leaderboard::leaderboard_view(&board, limit, player.as_ref().map(|p| p.user_id.as_str()))?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_body(serde_json::to_string(&view).expect("failed to serialize leaderboard")))
+        },
+        "/daily_status" => {
+            // A submission is only shown to the player who made it, while the puzzle is live too
+            let player = match auth::authenticate(&req)? {
+                Some(player) => player,
+                None => return Ok(unauthorized()),
+            };
+            let day = match request_day(&req) {
+                Ok(day) => day,
+                Err(response) => return Ok(response),
+            };
+
+            let status = get_daily_status(day, &player.user_id)?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_header(header::CACHE_CONTROL, "no-store")
+                .with_body(serde_json::to_string(&status).expect("failed to serialize daily status")))
+        },
+        "/register" => {
+            let body = req.into_body_str();
+            let request_data: serde_json::Value = match serde_json::from_str(&body) {
+                Ok(p) => p,
+                Err(_) => {
+                    return Ok(Response::from_status(StatusCode::BAD_REQUEST)
+                        .with_header("Access-Control-Allow-Origin", "*")
+                        .with_body_text_plain("Invalid JSON format"))
+                }
+            };
+
+            // Anonymous history can only be claimed by whoever holds its signed identity; an
+            // account's session doesn't count, or its history could be attached to a second account
+            let claim_user_id = match request_data["identity_token"].as_str() {
+                Some(token) => match auth::verify_identity(token)? {
+                    Some(user_id) => Some(user_id),
+                    None => return Ok(unauthorized()),
+                },
+                None => None,
+            };
+
+            let result = accounts::register(
+                request_data["username"].as_str().unwrap_or(""),
+                request_data["password"].as_str().unwrap_or(""),
+                request_data["display_name"].as_str().unwrap_or(""),
+                claim_user_id.as_deref(),
+            )?;
+            match result {
+                Ok(session) => Ok(Response::from_status(StatusCode::OK)
+                    .with_content_type(mime::APPLICATION_JSON)
+                    .with_header("Access-Control-Allow-Origin", "*")
+                    .with_body(serde_json::to_string(&session).expect("failed to serialize session"))),
+                Err(e) => Ok(e.into_response()),
+            }
+        },
+        "/login" => {
+            let client_ip = req.get_client_ip_addr();
+            let body = req.into_body_str();
+            let request_data: serde_json::Value = match serde_json::from_str(&body) {
+                Ok(p) => p,
+                Err(_) => {
+                    return Ok(Response::from_status(StatusCode::BAD_REQUEST)
+                        .with_header("Access-Control-Allow-Origin", "*")
+                        .with_body_text_plain("Invalid JSON format"))
+                }
+            };
+
+            let result = accounts::login(
+                request_data["username"].as_str().unwrap_or(""),
+                request_data["password"].as_str().unwrap_or(""),
+                client_ip,
+            )?;
+            match result {
+                Ok(session) => Ok(Response::from_status(StatusCode::OK)
+                    .with_content_type(mime::APPLICATION_JSON)
+                    .with_header("Access-Control-Allow-Origin", "*")
+                    .with_body(serde_json::to_string(&session).expect("failed to serialize session"))),
+                Err(e) => Ok(e.into_response()),
+            }
+        },
+        "/daily_usage" => {
+            let day = match request_day(&req) {
+                Ok(day) => day,
+                Err(response) => return Ok(response),
+            };
+
+            let daily_usage = get_daily_usage(day)?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_body(serde_json::to_string(&daily_usage).expect("failed to serialize daily usage")))
+        },
+        "/solution" => {
+            // Only reveal the answer once the puzzle is over everywhere
+            let day = match req.get_query_parameter("date").and_then(dates::parse_date) {
+                Some(day) => day,
+                None => return Ok(bad_request("Missing or invalid date")),
+            };
+            if day >= dates::earliest_current_day() {
+                return Ok(Response::from_status(StatusCode::FORBIDDEN)
+                    .with_header("Access-Control-Allow-Origin", "*")
+                    .with_body_text_plain("Solutions are published after the puzzle ends"));
+            }
+
+            let daily_solution = solution::get_daily_solution(day)?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_body(serde_json::to_string(&daily_solution).expect("failed to serialize solution")))
+        },
+        "/archive" => {
+            // Anyone can list past puzzles; which ones were played is only shown to that player
+            let player = auth::authenticate(&req)?;
+            let archive = archive::list_archive(player.as_ref().map(|p| p.user_id.as_str()))?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_header(header::CACHE_CONTROL, "no-store")
+                .with_body(serde_json::to_string(&archive).expect("failed to serialize archive")))
+        },
         "/calculate_overlap" => {
             // Parse POST body for player objects and teams
             let body = req.into_body_str();
@@ -411,6 +550,12 @@ fn main(req: Request) -> Result<Response, Error> {
                 .with_body(serde_json::to_string(&overlap_data).expect("failed to serialize overlap data")))
         },
         "/submit_daily" => {
+            // Players submit under the id in their signed session or identity token, never a raw user_id
+            let player = match auth::authenticate(&req)? {
+                Some(player) => player,
+                None => return Ok(unauthorized()),
+            };
+
             // Parse POST body for submission
             let body = req.into_body_str();
             let request_data: serde_json::Value = match serde_json::from_str(&body) {
@@ -424,26 +569,166 @@ fn main(req: Request) -> Result<Response, Error> {
             
             let players = request_data["players"].as_array()
                 .ok_or_else(|| Error::msg("Missing players array"))?
-                .iter()
-                .filter_map(|p| p.as_str().map(|s| s.to_string()))
-                .collect::<Vec<String>>();
+                .to_vec();
                 
             let date = request_data["date"].as_str()
-                .ok_or_else(|| Error::msg("Missing date"))?
-                .to_string();
+                .ok_or_else(|| Error::msg("Missing date"))?;
+
+            // Clients that roll over at their own midnight send their timezone with the submission
+            let tz = match dates::resolve_timezone(request_data["timezone"].as_str()) {
+                Ok(tz) => tz,
+                Err(message) => return Ok(bad_request(&message)),
+            };
                 
-            let user_id = request_data["user_id"].as_str()
-                .ok_or_else(|| Error::msg("Missing user_id"))?
-                .to_string();
-            
-            let result = submit_daily_solution(players, date, user_id)?;
+            let resolved = match validation::validate_submission(&players, date, tz)? {
+                Ok(resolved) => resolved,
+                Err(e) => return Ok(validation::error_response(&e)),
+            };
+
+            let day = dates::parse_date(date).ok_or_else(|| Error::msg("validated date failed to parse"))?;
+            let result = submit_daily_solution(resolved, day, player.user_id, player.display_name)?;
             Ok(Response::from_status(StatusCode::OK)
                 .with_content_type(mime::APPLICATION_JSON)
                 .with_header("Access-Control-Allow-Origin", "*")
                 .with_body(serde_json::to_string(&result).expect("failed to serialize submission result")))
         },
 
+        "/admin/puzzles" => {
+            if req.get_method() != Method::POST {
+                return Ok(Response::from_status(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header(header::ALLOW, "POST")
+                    .with_body_text_plain("This method is not allowed\n"));
+            }
+            if !auth::is_admin(&req)? {
+                return Ok(Response::from_status(StatusCode::UNAUTHORIZED)
+                    .with_body_text_plain("Missing or invalid admin token"));
+            }
+
+            let body = req.into_body_str();
+            let entries: Vec<admin::CuratedEntry> = match serde_json::from_str::<serde_json::Value>(&body)
+                .and_then(|mut calendar| serde_json::from_value(calendar["puzzles"].take()))
+            {
+                Ok(entries) => entries,
+                Err(_) => {
+                    return Ok(Response::from_status(StatusCode::BAD_REQUEST)
+                        .with_body_text_plain("Expected {\"puzzles\": [{\"date\", \"teams\", \"title\"}]}"))
+                }
+            };
+
+            let result = admin::store_curated_puzzles(entries)?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_body(serde_json::to_string(&result).expect("failed to serialize curation result")))
+        },
+
+        "/admin/datasets" => {
+            // GET lists the registry; POST {"action": "register" | "promote", "version": N} or {"action": "rollback"}
+            if req.get_method() != Method::GET && req.get_method() != Method::POST {
+                return Ok(Response::from_status(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header(header::ALLOW, "GET, POST")
+                    .with_body_text_plain("This method is not allowed\n"));
+            }
+            if !auth::is_admin(&req)? {
+                return Ok(Response::from_status(StatusCode::UNAUTHORIZED)
+                    .with_body_text_plain("Missing or invalid admin token"));
+            }
+
+            let store = kv_store::KVStore::open("journeyman")
+                .expect("failed to open KV store")
+                .unwrap();
+            let result = if req.get_method() == Method::GET {
+                Ok(registry::load_registry(&store)?)
+            } else {
+                let request_data: serde_json::Value = match serde_json::from_str(&req.into_body_str()) {
+                    Ok(data) => data,
+                    Err(_) => return Ok(bad_request("Expected a JSON body")),
+                };
+                let version = request_data["version"].as_u64().and_then(|v| u32::try_from(v).ok());
+                match (request_data["action"].as_str(), version) {
+                    (Some("register"), Some(version)) => registry::register(&store, version)?,
+                    (Some("promote"), Some(version)) => registry::promote(&store, version)?,
+                    (Some("rollback"), _) => registry::rollback(&store)?,
+                    _ => return Ok(bad_request("Expected an action of register, promote or rollback, with a version for the first two")),
+                }
+            };
+
+            match result {
+                Ok(registry) => Ok(Response::from_status(StatusCode::OK)
+                    .with_content_type(mime::APPLICATION_JSON)
+                    .with_body(serde_json::to_string(&registry).expect("failed to serialize dataset registry"))),
+                Err(e) => Ok(registry::error_response(&e)),
+            }
+        },
+
+        "/submit_archive" => {
+            // Replays of past puzzles are scored but kept off the live leaderboard
+            let player = match auth::authenticate(&req)? {
+                Some(player) => player,
+                None => return Ok(unauthorized()),
+            };
+
+            let body = req.into_body_str();
+            let request_data: serde_json::Value = match serde_json::from_str(&body) {
+                Ok(p) => p,
+                Err(_) => {
+                    return Ok(Response::from_status(StatusCode::BAD_REQUEST)
+                        .with_header("Access-Control-Allow-Origin", "*")
+                        .with_body_text_plain("Invalid JSON format"))
+                }
+            };
+
+            let players = request_data["players"].as_array()
+                .ok_or_else(|| Error::msg("Missing players array"))?
+                .to_vec();
+            let date = request_data["date"].as_str()
+                .ok_or_else(|| Error::msg("Missing date"))?;
+
+            let resolved = match validation::validate_archive_submission(&players, date)? {
+                Ok(resolved) => resolved,
+                Err(e) => return Ok(validation::error_response(&e)),
+            };
+
+            let day = dates::day_from_iso(date).ok_or_else(|| Error::msg("validated date failed to parse"))?;
+            let result = archive::submit_archive_play(resolved, day, &player.user_id)?;
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_body(serde_json::to_string(&result).expect("failed to serialize archive result")))
+        },
+
+        "/identity" => {
+            // Renew the identity the request carries, trade in a legacy id, or hand out a new one
+            let identity = if let Some(token) = auth::bearer_token(&req) {
+                match auth::renew_identity(token)? {
+                    Some(identity) => identity,
+                    None => return Ok(unauthorized()),
+                }
+            } else {
+                let body = req.into_body_str();
+                let request_data: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+                match request_data["legacy_user_id"].as_str() {
+                    Some(legacy_id) => match auth::exchange_legacy_id(legacy_id)? {
+                        Some(identity) => identity,
+                        None => {
+                            return Ok(Response::from_status(StatusCode::CONFLICT)
+                                .with_header("Access-Control-Allow-Origin", "*")
+                                .with_body_text_plain("That id can't be exchanged for an identity"))
+                        }
+                    },
+                    None => auth::issue_identity()?,
+                }
+            };
+            Ok(Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_header("Access-Control-Allow-Origin", "*")
+                .with_header(header::CACHE_CONTROL, "no-store")
+                .with_body(serde_json::to_string(&identity).expect("failed to serialize identity")))
+        },
         "/get_hint" => {
+            if auth::authenticate(&req)?.is_none() {
+                return Ok(unauthorized());
+            }
+
             // Parse POST body for teams and used_players
             let body = req.into_body_str();
             let request_data: serde_json::Value = match serde_json::from_str(&body) {
@@ -480,51 +765,73 @@ fn main(req: Request) -> Result<Response, Error> {
     }
 }
 
+fn bad_request(message: &str) -> Response {
+    Response::from_status(StatusCode::BAD_REQUEST)
+        .with_header("Access-Control-Allow-Origin", "*")
+        .with_body_text_plain(message)
+}
+
+// The timezone a request's "today" is in: the client's ?tz= if it sent one, else the rollover timezone
+fn request_timezone(req: &Request) -> Result<Tz, Response> {
+    dates::resolve_timezone(req.get_query_parameter("tz")).map_err(|message| bad_request(&message))
+}
+
+// The ?date= a request asks about (YYYY-MM-DD), defaulting to today in its timezone
+fn request_day(req: &Request) -> Result<u64, Response> {
+    let tz = request_timezone(req)?;
+    match req.get_query_parameter("date") {
+        Some(date) => dates::parse_date(date).ok_or_else(|| bad_request("Dates must be YYYY-MM-DD")),
+        None => Ok(dates::current_day_in(tz)),
+    }
+}
+
+fn unauthorized() -> Response {
+    Response::from_status(StatusCode::UNAUTHORIZED)
+        .with_header("Access-Control-Allow-Origin", "*")
+        .with_body_text_plain("Missing, invalid or expired identity token")
+}
+
 // Generate a hint for a player who fits all remaining teams and is not used
 fn generate_hint(teams: &[String], used_players: &[String]) -> Result<serde_json::Value, Error> {
-    let player_data = get(2)?;
-    let mut best_player: Option<serde_json::Value> = None;
+    let mut player_store = PlayerStore::open()?;
+    let mut best_id: Option<String> = None;
     let mut best_count = 0;
 
     // Find the player who satisfies the most teams and is not used
-    if let Some(teams_obj) = player_data["teams"].as_object() {
-        // Build a map of team_code -> Vec<PlayerInfo>
-        let mut all_players: Vec<serde_json::Value> = Vec::new();
-        let mut player_team_map: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
-        for team in teams {
-            let team_code = team_code_from_name(team);
-            if let Some(code) = team_code {
-                if let Some(players_array) = teams_obj.get(code).and_then(|v| v.as_array()) {
-                    for p in players_array {
-                        let pid = p.get("id").and_then(|id| id.as_str()).unwrap_or("");
-                        if !used_players.iter().any(|u| u.eq(pid)) {
-                            let entry = player_team_map.entry(pid.to_string()).or_insert_with(Vec::new);
-                            if !entry.contains(team) {
-                                entry.push(team.clone());
-                            }
-                            all_players.push(p.clone());
-                        }
+    let mut all_players: Vec<String> = Vec::new();
+    let mut player_team_map: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+    for team in teams {
+        let team_code = team_code_from_name(team);
+        if let Some(code) = team_code {
+            for id in player_store.roster(code)? {
+                if !used_players.iter().any(|u| u.eq(id)) {
+                    let entry = player_team_map.entry(id.clone()).or_default();
+                    if !entry.contains(team) {
+                        entry.push(team.clone());
                     }
+                    all_players.push(id.clone());
                 }
             }
         }
-        // Find the player who covers the most teams
-        let mut checked = std::collections::HashSet::new();
-        for p in all_players {
-            let pid = p.get("id").and_then(|n| n.as_str()).unwrap_or("").to_string();
-            if checked.contains(&pid) { continue; }
-            checked.insert(pid.clone());
-            let count = player_team_map.get(&pid).map(|v| v.len()).unwrap_or(0);
-            if count > best_count {
-                best_count = count;
-                best_player = Some(p.clone());
-            }
+    }
+    // Find the player who covers the most teams
+    let mut checked = std::collections::HashSet::new();
+    for id in all_players {
+        if !checked.insert(id.clone()) { continue; }
+        let count = player_team_map.get(&id).map(|v| v.len()).unwrap_or(0);
+        if count > best_count {
+            best_count = count;
+            best_id = Some(id);
         }
     }
 
+    let best_player: Option<PlayerEntry> = match &best_id {
+        Some(id) => player_store.player(id)?,
+        None => None,
+    };
     println!("The best player is {:?} who fits {} teams", best_player, best_count);
 
-    let id = best_player.as_ref().and_then(|p| p.get("id")).and_then(|id| id.as_str()).unwrap_or("0");
+    let id = best_id.as_deref().unwrap_or("0");
     let url = format!("https://api-web.nhle.com/v1/player/{}/landing", id);
 
     println!("Fetching player details from URL: {}", url);
@@ -540,7 +847,7 @@ fn generate_hint(teams: &[String], used_players: &[String]) -> Result<serde_json
 
     // Generate hints
     let mut hints = Vec::new();
-    if let Some(player) = &best_player {
+    if best_id.is_some() {
         if best_count < teams.len() {
             hints.push(format!("This player fits {} out of {} teams.", best_count, teams.len()));
         }
@@ -626,8 +933,8 @@ fn generate_hint(teams: &[String], used_players: &[String]) -> Result<serde_json
             }
         }
         
-        // 9. Career points/save percentage
-        if let Some(career_totals) = player.get("careerTotals").and_then(|s| s.as_object()) {
+        // 9. Career points/save percentage (the dataset only has bio details, so these come from the NHL API)
+        if let Some(career_totals) = player_details.get("careerTotals").and_then(|s| s.as_object()) {
             if let Some(regular_season) = career_totals.get("regularSeason").and_then(|r| r.as_object()) {
                 if let Some(points) = regular_season.get("points").and_then(|p| p.as_i64()) {
                     hints.push(format!("Career regular season points: {}", points));
@@ -644,18 +951,5 @@ fn generate_hint(teams: &[String], used_players: &[String]) -> Result<serde_json
 
 // Helper: get team code from name
 fn team_code_from_name(name: &str) -> Option<&'static str> {
-    match name {
-        "Anaheim Ducks" => Some("ANA"), "Boston Bruins" => Some("BOS"), "Buffalo Sabres" => Some("BUF"),
-        "Calgary Flames" => Some("CGY"), "Carolina Hurricanes" => Some("CAR"), "Chicago Blackhawks" => Some("CHI"),
-        "Colorado Avalanche" => Some("COL"), "Columbus Blue Jackets" => Some("CBJ"), "Dallas Stars" => Some("DAL"),
-        "Detroit Red Wings" => Some("DET"), "Edmonton Oilers" => Some("EDM"), "Florida Panthers" => Some("FLA"),
-        "Los Angeles Kings" => Some("LAK"), "Minnesota Wild" => Some("MIN"), "Montreal Canadiens" => Some("MTL"),
-        "Nashville Predators" => Some("NSH"), "New Jersey Devils" => Some("NJD"), "New York Islanders" => Some("NYI"),
-        "New York Rangers" => Some("NYR"), "Ottawa Senators" => Some("OTT"), "Philadelphia Flyers" => Some("PHI"),
-        "Pittsburgh Penguins" => Some("PIT"), "San Jose Sharks" => Some("SJS"), "Seattle Kraken" => Some("SEA"),
-        "St. Louis Blues" => Some("STL"), "Tampa Bay Lightning" => Some("TBL"), "Toronto Maple Leafs" => Some("TOR"),
-        "Utah Hockey Club" => Some("UTA"), "Vancouver Canucks" => Some("VAN"), "Vegas Golden Knights" => Some("VGK"),
-        "Washington Capitals" => Some("WSH"), "Winnipeg Jets" => Some("WPG"),
-        _ => None,
-    }
+    teams::by_name(name).map(|team| team.code)
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! Players and team rosters read from the split dataset.
+//!
+//! Most requests only touch a few players, so instead of parsing the whole dataset they read the
+//! `player:{id}` and `team:{code}` entries (see `journeyman_core::dataset`) of the version the
+//! registry says is current. The full blob is still what `/get_players` and `/get_playersv2`
+//! serve, and what lookups by name fall back to. Before any version has been promoted there are
+//! no split entries, and the unversioned blob is read whole instead.
+
+use fastly::kv_store::{self, KVStore, KVStoreError};
+use fastly::Error;
+use journeyman_core::dataset::{self, DatasetManifest, PlayerDatabase, PlayerEntry, TeamEntry};
+use journeyman_core::registry::DatasetVersion;
+use std::collections::HashMap;
+
+enum Source {
+    // The registered version being served, which decides the key prefix
+    Split(DatasetVersion),
+    // The unversioned dataset, parsed
+    Full(PlayerDatabase),
+}
+
+pub struct PlayerStore {
+    store: KVStore,
+    source: Source,
+    // Rosters already fetched by this request
+    rosters: HashMap<String, Vec<String>>,
+}
+
+impl PlayerStore {
+    // Fails if the published entries are from a schema this build doesn't read
+    pub fn open() -> Result<PlayerStore, Error> {
+        let store = kv_store::KVStore::open("journeyman")
+            .expect("failed to open KV store")
+            .unwrap();
+        let version = crate::registry::current(&store)?;
+        if crate::registry::is_unregistered(&version) {
+            let database = crate::registry::load(&store, &version)?;
+            return Ok(PlayerStore {
+                store,
+                source: Source::Full(database),
+                rosters: HashMap::new(),
+            });
+        }
+
+        let mut res = store.lookup(&version.key(dataset::MANIFEST_KEY))?;
+        let manifest: DatasetManifest = serde_json::from_str(&res.take_body().into_string())?;
+        if manifest.schema_version != dataset::SCHEMA_VERSION {
+            return Err(Error::msg(format!(
+                "published dataset is schema version {}, expected {}",
+                manifest.schema_version,
+                dataset::SCHEMA_VERSION
+            )));
+        }
+
+        Ok(PlayerStore {
+            store,
+            source: Source::Split(version),
+            rosters: HashMap::new(),
+        })
+    }
+
+    // None only if the dataset has no such player; a failed lookup is an error, not an unknown player
+    pub fn player(&self, id: &str) -> Result<Option<PlayerEntry>, Error> {
+        let version = match &self.source {
+            Source::Split(version) => version,
+            Source::Full(database) => return Ok(database.player_entry(id)),
+        };
+        match self.store.lookup(&version.key(&dataset::player_key(id))) {
+            Ok(mut res) => Ok(Some(serde_json::from_str(&res.take_body().into_string())?)),
+            Err(KVStoreError::ItemNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Ids of everyone who played for a team; empty for a team the dataset doesn't have
+    pub fn roster(&mut self, code: &str) -> Result<&[String], Error> {
+        if !self.rosters.contains_key(code) {
+            let players = match &self.source {
+                Source::Split(version) => match self.store.lookup(&version.key(&dataset::team_key(code))) {
+                    Ok(mut res) => serde_json::from_str::<TeamEntry>(&res.take_body().into_string())?.players,
+                    Err(KVStoreError::ItemNotFound) => Vec::new(),
+                    Err(e) => return Err(e.into()),
+                },
+                Source::Full(database) => database.teams.get(code).cloned().unwrap_or_default(),
+            };
+            self.rosters.insert(code.to_string(), players);
+        }
+        Ok(&self.rosters[code])
+    }
+}
@@ -0,0 +1,248 @@
+//! Daily puzzle selection with difficulty control.
+//!
+//! Each day gets a sequence of candidate team sets from a keyed shuffle (see
+//! `journeyman_core::schedule`) salted with a secret, so future puzzles can't be predicted.
+//! Candidates used within the repeat window are skipped; the rest are solved exactly and the first
+//! one whose minimum cover size and number of optimal covers fall inside the configured difficulty
+//! band becomes the puzzle. The choice is cached in KV under `daily_puzzle_{day}` so the rosters
+//! only have to be read once per day, and recorded in `puzzle_history` for the repeat check.
+//! A hand-picked puzzle stored under `curated_puzzle_{day}` (see `admin`) takes precedence.
+//!
+//! Days from before this selector was rolled out were played with the original generator, so
+//! when one of them is replayed from the archive its teams come from `schedule::legacy_teams`.
+//! The rollout day is recorded under `selector_rollout_day` the first time it's needed.
+
+use crate::kv::Versioned;
+use crate::players::PlayerStore;
+use fastly::config_store::ConfigStore;
+use fastly::kv_store;
+use fastly::secret_store::SecretStore;
+use fastly::Error;
+use journeyman_core::schedule::{self, ScheduleHistory};
+use journeyman_core::solver::{self, difficulty_label, DifficultyBand};
+use journeyman_core::teams;
+use journeyman_core::versioned::{self, VersionedStore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+const CONFIG_STORE: &str = "journeyman_config";
+const SECRET_STORE: &str = "journeyman_secrets";
+const SALT_NAME: &str = "schedule_salt";
+const HISTORY_KEY: &str = "puzzle_history";
+const ROLLOUT_KEY: &str = "selector_rollout_day";
+const TEAMS_PER_PUZZLE: usize = 8;
+// A team set can't come back until this many days have passed, unless configured otherwise
+const DEFAULT_REPEAT_WINDOW_DAYS: u64 = 365;
+// Candidates to solve before settling for the one closest to the band
+const MAX_REROLLS: u32 = 24;
+// Candidates to draw in total, counting ones skipped as repeats
+const MAX_CANDIDATES: u32 = 1000;
+// Lost races on the history entry before giving up
+const MAX_HISTORY_ATTEMPTS: u32 = 16;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Puzzle {
+    pub teams: Vec<String>,
+    pub difficulty: String,
+    pub min_players: usize,
+    pub optimal_covers: u64,
+    pub attempt: u32,
+    #[serde(default)]
+    pub curated: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+// The difficulty band from the `difficulty_band` config store key, or the default
+fn difficulty_band() -> DifficultyBand {
+    let configured = ConfigStore::try_open(CONFIG_STORE)
+        .ok()
+        .and_then(|store| store.get("difficulty_band"));
+    match configured {
+        Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            println!("Ignoring invalid difficulty_band {}: {}", json, e);
+            DifficultyBand::default()
+        }),
+        None => DifficultyBand::default(),
+    }
+}
+
+fn schedule_salt() -> Result<Vec<u8>, Error> {
+    let store = SecretStore::open(SECRET_STORE)?;
+    let secret = store.get(SALT_NAME)
+        .ok_or_else(|| Error::msg(format!("secret store is missing {}", SALT_NAME)))?;
+    Ok(secret.plaintext().to_vec())
+}
+
+fn repeat_window_days() -> u64 {
+    ConfigStore::try_open(CONFIG_STORE)
+        .ok()
+        .and_then(|store| store.get("repeat_window_days"))
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_REPEAT_WINDOW_DAYS)
+}
+
+fn parse_history(body: Option<&str>, window_days: u64) -> ScheduleHistory {
+    let entries: HashMap<String, u64> = body
+        .and_then(|body| serde_json::from_str(body).ok())
+        .unwrap_or_default();
+    ScheduleHistory::with_entries(window_days, entries)
+}
+
+fn load_history(store: &kv_store::KVStore, window_days: u64) -> Result<ScheduleHistory, Error> {
+    let current = Versioned(store).read(HISTORY_KEY)?;
+    Ok(parse_history(current.as_ref().map(|(body, _)| body.as_str()), window_days))
+}
+
+// Note that a day's puzzle uses `teams` so generated days keep clear of it. Puzzles for
+// different days can be chosen at once, so this is a generation-checked update like the usage
+// counts rather than a plain overwrite.
+pub fn record_in_history(store: &kv_store::KVStore, teams: &[String], day: u64) -> Result<(), Error> {
+    let window_days = repeat_window_days();
+    let teams: Vec<&str> = teams.iter().map(|team| team.as_str()).collect();
+    versioned::update(&Versioned(store), HISTORY_KEY, MAX_HISTORY_ATTEMPTS, |current| {
+        let mut history = parse_history(current, window_days);
+        history.record(&teams, day);
+        let entries: HashMap<&str, u64> = history.entries().collect();
+        Ok::<_, Infallible>((serde_json::to_string(&entries).expect("history always serializes"), ()))
+    })?.unwrap_or_else(|never| match never {});
+    Ok(())
+}
+
+pub fn curated_key(day: u64) -> String {
+    format!("curated_puzzle_{}", day)
+}
+
+// Team code -> player ids for the candidate's teams, in the candidate's order
+fn team_players(player_store: &mut PlayerStore, teams: &[&str]) -> Result<Vec<Vec<String>>, Error> {
+    let mut players = Vec::new();
+    for team in teams {
+        players.push(match crate::team_code_from_name(team) {
+            Some(code) => player_store.roster(code)?.to_vec(),
+            None => Vec::new(),
+        });
+    }
+    Ok(players)
+}
+
+// Exact solution for a set of teams against the dataset. The outer error is a failure to read
+// the rosters; the inner one means the teams can't be covered.
+pub fn evaluate(player_store: &mut PlayerStore, teams: &[&str]) -> Result<Result<solver::CoverSolution, solver::SolveError>, Error> {
+    Ok(solver::solve(&team_players(player_store, teams)?, 0))
+}
+
+fn select_puzzle(day: u64, history: &ScheduleHistory) -> Result<Puzzle, Error> {
+    let mut player_store = PlayerStore::open()?;
+    let salt = schedule_salt()?;
+    let pool: Vec<&str> = teams::TEAMS.iter().map(|team| team.name).collect();
+    let band = difficulty_band();
+    let mut closest: Option<(u64, Puzzle)> = None;
+    let mut solved = 0;
+
+    for attempt in 0..MAX_CANDIDATES {
+        let teams = schedule::candidate(&salt, &pool, day, attempt, TEAMS_PER_PUZZLE);
+        if history.is_repeat(&teams, day) {
+            continue;
+        }
+        if solved == MAX_REROLLS {
+            break;
+        }
+        solved += 1;
+
+        let solution = match evaluate(&mut player_store, &teams)? {
+            Ok(solution) => solution,
+            Err(e) => {
+                println!("Skipping candidate {} for day {}: {}", attempt, day, e);
+                continue;
+            }
+        };
+
+        let distance = band.distance(solution.min_players, solution.optimal_covers);
+        let puzzle = Puzzle {
+            teams: teams.iter().map(|team| team.to_string()).collect(),
+            difficulty: difficulty_label(solution.min_players, solution.optimal_covers).to_string(),
+            min_players: solution.min_players,
+            optimal_covers: solution.optimal_covers,
+            attempt,
+            curated: false,
+            title: None,
+        };
+        if distance == 0 {
+            return Ok(puzzle);
+        }
+        if closest.as_ref().is_none_or(|(best, _)| distance < *best) {
+            closest = Some((distance, puzzle));
+        }
+    }
+
+    println!("No candidate for day {} landed in the difficulty band; using the closest", day);
+    closest.map(|(_, puzzle)| puzzle)
+        .ok_or_else(|| Error::msg(format!("no solvable candidate puzzle for day {}", day)))
+}
+
+// The first day this selector chose. The first time it's asked for, every day that was already
+// over everywhere had been played with the original generator.
+fn rollout_day(store: &kv_store::KVStore) -> Result<u64, Error> {
+    let versioned = Versioned(store);
+    let recorded = || -> Result<Option<u64>, Error> {
+        Ok(versioned.read(ROLLOUT_KEY)?.and_then(|(body, _)| body.parse().ok()))
+    };
+    if let Some(day) = recorded()? {
+        return Ok(day);
+    }
+    let day = crate::dates::earliest_current_day();
+    if versioned.write_if(ROLLOUT_KEY, &day.to_string(), None)? {
+        return Ok(day);
+    }
+    // Another request recorded it first
+    Ok(recorded()?.unwrap_or(day))
+}
+
+// A day as the original generator served it
+fn legacy_puzzle(day: u64) -> Result<Puzzle, Error> {
+    let teams = schedule::legacy_teams(day);
+    let solution = evaluate(&mut PlayerStore::open()?, &teams)?
+        .map_err(|e| Error::msg(format!("legacy puzzle for day {} can't be solved: {}", day, e)))?;
+    Ok(Puzzle {
+        teams: teams.iter().map(|team| team.to_string()).collect(),
+        difficulty: difficulty_label(solution.min_players, solution.optimal_covers).to_string(),
+        min_players: solution.min_players,
+        optimal_covers: solution.optimal_covers,
+        attempt: 0,
+        curated: false,
+        title: None,
+    })
+}
+
+// The puzzle for a day, selecting and caching it on first use
+pub fn daily_puzzle(day: u64) -> Result<Puzzle, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    if let Ok(mut res) = store.lookup(&curated_key(day)) {
+        if let Ok(puzzle) = serde_json::from_str(&res.take_body().into_string()) {
+            return Ok(puzzle);
+        }
+    }
+
+    let key = format!("daily_puzzle_{}", day);
+    if let Ok(mut res) = store.lookup(&key) {
+        if let Ok(puzzle) = serde_json::from_str(&res.take_body().into_string()) {
+            return Ok(puzzle);
+        }
+    }
+
+    if day < rollout_day(&store)? {
+        let puzzle = legacy_puzzle(day)?;
+        store.insert(&key, serde_json::to_string(&puzzle)?)?;
+        return Ok(puzzle);
+    }
+
+    let puzzle = select_puzzle(day, &load_history(&store, repeat_window_days())?)?;
+    store.insert(&key, serde_json::to_string(&puzzle)?)?;
+    record_in_history(&store, &puzzle.teams, day)?;
+
+    Ok(puzzle)
+}
@@ -0,0 +1,146 @@
+//! The dataset registry in KV: which published dataset versions exist and which one is served.
+//!
+//! The registry itself (`journeyman_core::registry`) is one JSON entry, updated with the same
+//! generation-checked writes (`journeyman_core::versioned`) as the usage counts so two admins
+//! can't undo each other's changes.
+//!
+//! Until a version has been promoted the service keeps reading the dataset it always has: the
+//! version 2 blob under `playersv2`, upgraded as it's parsed. That layout has no split entries,
+//! so `PlayerStore` reads it whole.
+
+use crate::kv::Versioned;
+use fastly::http::StatusCode;
+use fastly::kv_store::KVStore;
+use fastly::{mime, Error, Response};
+use journeyman_core::dataset::{self, DatasetManifest, PlayerDatabase};
+use journeyman_core::registry::{self, DatasetRegistry, DatasetVersion, RegistryError, REGISTRY_KEY};
+use journeyman_core::versioned::{self, VersionedStore};
+
+// An admin change losing this many races in a row means something is wrong
+const MAX_ATTEMPTS: u32 = 8;
+
+// Key of the dataset from before versions were registered
+const UNREGISTERED_BLOB_KEY: &str = "playersv2";
+
+// Where the dataset lived before versions were registered
+fn unregistered() -> DatasetVersion {
+    DatasetVersion {
+        schema_version: 2,
+        generated_at: String::new(),
+        registered_at: String::new(),
+        player_count: 0,
+        blob_key: UNREGISTERED_BLOB_KEY.to_string(),
+        key_prefix: String::new(),
+        blob_chunks: 0,
+    }
+}
+
+// True for the unversioned dataset, which only exists as a full blob
+pub fn is_unregistered(version: &DatasetVersion) -> bool {
+    version.blob_key == UNREGISTERED_BLOB_KEY
+}
+
+// The registry and its generation, or None before anything has been registered
+fn read_registry(store: &KVStore) -> Result<Option<(DatasetRegistry, u64)>, Error> {
+    match Versioned(store).read(REGISTRY_KEY)? {
+        Some((body, generation)) => Ok(Some((serde_json::from_str(&body)?, generation))),
+        None => Ok(None),
+    }
+}
+
+pub fn load_registry(store: &KVStore) -> Result<DatasetRegistry, Error> {
+    Ok(read_registry(store)?.map(|(registry, _)| registry).unwrap_or_default())
+}
+
+// The version being served. Registering a version doesn't serve it, so until one is promoted
+// this is still the unversioned dataset.
+pub fn current(store: &KVStore) -> Result<DatasetVersion, Error> {
+    Ok(load_registry(store)?
+        .current_version()
+        .map(|(_, version)| version.clone())
+        .unwrap_or_else(unregistered))
+}
+
+// The full dataset for a version, put back together if it was uploaded in pieces
+pub fn load(store: &KVStore, version: &DatasetVersion) -> Result<PlayerDatabase, Error> {
+    if version.blob_chunks == 0 {
+        let mut res = store.lookup(&version.blob_key)?;
+        return Ok(PlayerDatabase::from_json(&res.take_body().into_string())?);
+    }
+    let mut json = String::new();
+    for index in 0..version.blob_chunks {
+        let mut res = store.lookup(&version.key(&registry::blob_chunk_key(index)))?;
+        json.push_str(&res.take_body().into_string());
+    }
+    Ok(PlayerDatabase::from_json(&json)?)
+}
+
+// Why an update didn't write anything
+enum Refused {
+    Rejected(RegistryError),
+    Unreadable(serde_json::Error),
+}
+
+// Apply `change` to the registry and write it back, starting over if someone else wrote first
+fn update(
+    store: &KVStore,
+    change: impl Fn(&mut DatasetRegistry) -> Result<(), RegistryError>,
+) -> Result<Result<DatasetRegistry, RegistryError>, Error> {
+    let updated = versioned::update(&Versioned(store), REGISTRY_KEY, MAX_ATTEMPTS, |current| {
+        let mut registry = match current {
+            Some(body) => serde_json::from_str(body).map_err(Refused::Unreadable)?,
+            None => DatasetRegistry::default(),
+        };
+        change(&mut registry).map_err(Refused::Rejected)?;
+        let body = serde_json::to_string(&registry).expect("failed to serialize registry");
+        Ok((body, registry))
+    })?;
+    match updated {
+        Ok(registry) => Ok(Ok(registry)),
+        Err(Refused::Rejected(e)) => Ok(Err(e)),
+        Err(Refused::Unreadable(e)) => Err(e.into()),
+    }
+}
+
+// The manifest uploaded under a version's prefix, if it's there and this build can read it
+fn uploaded_manifest(store: &KVStore, version: u32) -> Result<Option<DatasetManifest>, Error> {
+    let key = format!("{}{}", DatasetVersion::key_prefix_for(version), dataset::MANIFEST_KEY);
+    Ok(match Versioned(store).read(&key)? {
+        Some((body, _)) => serde_json::from_str::<DatasetManifest>(&body).ok()
+            .filter(|manifest| manifest.schema_version == dataset::SCHEMA_VERSION),
+        None => None,
+    })
+}
+
+// Add a version whose keys have been uploaded. It isn't served until it's promoted.
+pub fn register(store: &KVStore, version: u32) -> Result<Result<DatasetRegistry, RegistryError>, Error> {
+    let manifest = match uploaded_manifest(store, version)? {
+        Some(manifest) => manifest,
+        None => return Ok(Err(RegistryError::Incomplete(version))),
+    };
+    let entry = DatasetVersion::new(version, &manifest, crate::dates::now_rfc3339());
+    update(store, |registry| registry.register(version, entry.clone()))
+}
+
+// A rejected admin change, as the response to send back
+pub fn error_response(error: &RegistryError) -> Response {
+    let status = match error {
+        RegistryError::UnknownVersion(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::CONFLICT,
+    };
+    let body = serde_json::json!({
+        "error": error.code(),
+        "message": error.to_string()
+    });
+    Response::from_status(status)
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_body(serde_json::to_string(&body).expect("failed to serialize registry error"))
+}
+
+pub fn promote(store: &KVStore, version: u32) -> Result<Result<DatasetRegistry, RegistryError>, Error> {
+    update(store, |registry| registry.promote(version))
+}
+
+pub fn rollback(store: &KVStore) -> Result<Result<DatasetRegistry, RegistryError>, Error> {
+    update(store, |registry| registry.rollback().map(|_| ()))
+}
@@ -0,0 +1,67 @@
+//! Optimal answers for finished daily puzzles, from the exact solver in `journeyman-core`.
+
+use crate::players::PlayerStore;
+use fastly::kv_store;
+use fastly::Error;
+use journeyman_core::solver;
+use std::collections::HashMap;
+
+// How many optimal covers to show players
+const SAMPLE_COVERS: usize = 5;
+
+fn solution_key(date: &str) -> String {
+    format!("daily_solution_{}", date)
+}
+
+// Solve a day's puzzle. Puzzles never change once published, so the answer is cached in KV.
+pub fn get_daily_solution(day: u64) -> Result<serde_json::Value, Error> {
+    let store = kv_store::KVStore::open("journeyman")
+        .expect("failed to open KV store")
+        .unwrap();
+
+    let date = day.to_string();
+    if let Ok(mut res) = store.lookup(&solution_key(&date)) {
+        return Ok(serde_json::from_str(&res.take_body().into_string())?);
+    }
+
+    let mut player_store = PlayerStore::open()?;
+    let teams = crate::puzzle::daily_puzzle(day)?.teams;
+
+    let mut team_players: Vec<Vec<String>> = Vec::new();
+    for team in &teams {
+        let code = crate::team_code_from_name(team)
+            .ok_or_else(|| Error::msg(format!("no team code for {}", team)))?;
+        team_players.push(player_store.roster(code)?.to_vec());
+    }
+
+    let solution = solver::solve(&team_players, SAMPLE_COVERS)?;
+    // Names for just the players shown
+    let mut names: HashMap<String, String> = HashMap::new();
+    for id in solution.samples.iter().flatten() {
+        if !names.contains_key(id) {
+            if let Some(entry) = player_store.player(id)? {
+                names.insert(id.clone(), entry.info.name);
+            }
+        }
+    }
+    let sample_covers: Vec<Vec<serde_json::Value>> = solution.samples.iter()
+        .map(|cover| {
+            cover.iter()
+                .map(|id| serde_json::json!({"id": id, "name": names.get(id)}))
+                .collect()
+        })
+        .collect();
+
+    let response = serde_json::json!({
+        "date": crate::dates::iso_from_day(day),
+        "puzzle_number": crate::archive::puzzle_number(day),
+        "teams": teams,
+        "min_players": solution.min_players,
+        "optimal_covers": solution.optimal_covers,
+        "exhaustive": solution.exhaustive,
+        "sample_covers": sample_covers
+    });
+    store.insert(&solution_key(&date), serde_json::to_string(&response)?)?;
+
+    Ok(response)
+}
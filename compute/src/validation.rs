@@ -0,0 +1,95 @@
+//! Server-side checks for daily submissions, so a hand-crafted POST can't land on the leaderboard.
+//!
+//! The date checks are here; what makes a list of players an answer is `journeyman_core::submission`.
+
+use crate::archive;
+use crate::dates;
+use chrono_tz::Tz;
+use fastly::http::StatusCode;
+use crate::players::PlayerStore;
+use journeyman_core::dataset::PlayerDatabase;
+use journeyman_core::submission::{self, ResolvedPlayer, SubmissionError};
+use fastly::{mime, Error, Response};
+
+// A rejected submission, as the response to send back
+pub fn error_response(error: &SubmissionError) -> Response {
+    Response::from_status(StatusCode::BAD_REQUEST)
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_header("Access-Control-Allow-Origin", "*")
+        .with_body(serde_json::to_string(&error.to_json()).expect("failed to serialize submission error"))
+}
+
+// Collect every dataset entry matching a submitted id (preferred) or name, with the teams each played for.
+// Names need the whole dataset, which is loaded into `player_data` the first time one comes up.
+fn find_candidates(
+    player_store: &PlayerStore,
+    player_data: &mut Option<PlayerDatabase>,
+    id: Option<&str>,
+    name: Option<&str>,
+) -> Result<Vec<ResolvedPlayer>, Error> {
+    let entries = match (id, name) {
+        (Some(id), _) => player_store.player(id)?.into_iter().collect(),
+        (None, Some(name)) => {
+            if player_data.is_none() {
+                *player_data = Some(crate::load_dataset()?);
+            }
+            let data = player_data.as_ref().expect("dataset was just loaded");
+            data.players.values()
+                .filter(|p| p.name.eq_ignore_ascii_case(name))
+                .filter_map(|p| data.player_entry(&p.id))
+                .collect()
+        }
+        (None, None) => Vec::new(),
+    };
+
+    Ok(entries.into_iter()
+        .map(|entry| ResolvedPlayer {
+            id: entry.info.id,
+            name: entry.info.name,
+            teams: entry.teams,
+        })
+        .collect())
+}
+
+// Resolve every submitted player against the dataset and confirm they cover all of the day's teams.
+// The outer error is an infrastructure failure; the inner one is a rejected submission.
+pub fn validate_submission(players: &[serde_json::Value], date: &str, tz: Tz) -> Result<Result<Vec<ResolvedPlayer>, SubmissionError>, Error> {
+    let day = match dates::parse_date(date) {
+        Some(day) => day,
+        None => return Ok(Err(SubmissionError::InvalidDate(date.to_string()))),
+    };
+    // Today in the submitter's timezone, so a puzzle stays open until their own midnight
+    let today = dates::current_day_in(tz);
+    if day != today {
+        return Ok(Err(SubmissionError::WrongDate {
+            submitted: date.to_string(),
+            expected: dates::iso_from_day(today),
+        }));
+    }
+
+    validate_players(players, day)
+}
+
+// Same checks for a replay of a past puzzle, which is dated YYYY-MM-DD
+pub fn validate_archive_submission(players: &[serde_json::Value], date: &str) -> Result<Result<Vec<ResolvedPlayer>, SubmissionError>, Error> {
+    let day = match dates::day_from_iso(date) {
+        Some(day) => day,
+        None => return Ok(Err(SubmissionError::InvalidDate(date.to_string()))),
+    };
+    if !archive::is_archived(day) {
+        return Ok(Err(SubmissionError::NotInArchive(date.to_string())));
+    }
+
+    validate_players(players, day)
+}
+
+// The rules themselves are in `journeyman_core::submission`; this supplies the day's teams and
+// the dataset lookups
+fn validate_players(players: &[serde_json::Value], day: u64) -> Result<Result<Vec<ResolvedPlayer>, SubmissionError>, Error> {
+    let puzzle_teams = crate::puzzle::daily_puzzle(day)?.teams;
+    let player_store = PlayerStore::open()?;
+    let mut player_data = None;
+    submission::validate_players(players, &puzzle_teams, |id, name| {
+        find_candidates(&player_store, &mut player_data, id, name)
+    })
+}
@@ -0,0 +1,356 @@
+//! The player dataset written by `nhl-player-db` and served by the edge service.
+//!
+//! Both sides use these types, so a change to the format has to happen here and shows up as a
+//! compile error (or a parse error against an old file) rather than silently missing fields.
+//!
+//! Since version 3 each player is stored once and teams refer to players by id, so looking up a
+//! player or their teams doesn't mean scanning every roster. The older shapes are still produced
+//! from it for clients that read them.
+//!
+//! The edge service can also read the dataset split into small KV entries, `player:{id}` and
+//! `team:{code}` plus a manifest, so a request fetches only the players and teams it touches.
+//! Which published copy it reads is up to the `registry`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of `PlayerDatabase` this code reads and writes. Bump it whenever the format changes.
+pub const SCHEMA_VERSION: u32 = 3;
+
+// Files from before the version field existed are version 2
+fn unversioned() -> u32 {
+    2
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct PlayerInfo {
+    /// NHL player id
+    pub id: String,
+    pub name: String,
+    pub birth_date: Option<String>,
+    /// "City, Country"
+    pub birth_place: Option<String>,
+    pub position: Option<String>,
+}
+
+/// Every player once, keyed by id, with each team listing the ids of the players who played for
+/// it. Build one with `new` and `insert` so the player-to-teams index stays in step.
+///
+/// The maps are ordered, so the same dataset always serializes to the same bytes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerDatabase {
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
+    pub players: BTreeMap<String, PlayerInfo>,
+    /// Player ids by current team code
+    pub teams: BTreeMap<String, Vec<String>>,
+    pub generated_at: String,
+    pub seasons_covered: Vec<String>,
+    // Team codes by player id, rebuilt on load
+    #[serde(skip)]
+    player_teams: BTreeMap<String, Vec<String>>,
+}
+
+/// The version 2 dataset: full player details repeated under every team they played for.
+/// Still served to web clients that expect it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerDatabaseV2 {
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
+    pub teams: BTreeMap<String, Vec<PlayerInfo>>,
+    pub generated_at: String,
+    pub seasons_covered: Vec<String>,
+}
+
+/// The original dataset, player names only. Still served to older web clients.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegacyPlayerDatabase {
+    pub teams: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub generated_at: Option<String>,
+    #[serde(default)]
+    pub seasons_covered: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum DatasetError {
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatasetError::Parse(e) => write!(f, "dataset doesn't match the schema: {}", e),
+            DatasetError::UnsupportedVersion(version) => {
+                write!(f, "dataset is schema version {}, expected {}", version, SCHEMA_VERSION)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {}
+
+// Just enough of a dataset to tell which version it is
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default = "unversioned")]
+    schema_version: u32,
+}
+
+impl PlayerDatabase {
+    pub fn new(generated_at: String, seasons_covered: Vec<String>) -> PlayerDatabase {
+        PlayerDatabase {
+            schema_version: SCHEMA_VERSION,
+            players: BTreeMap::new(),
+            teams: BTreeMap::new(),
+            generated_at,
+            seasons_covered,
+            player_teams: BTreeMap::new(),
+        }
+    }
+
+    /// Parse a dataset, upgrading a version 2 file and rejecting any other version.
+    pub fn from_json(json: &str) -> Result<PlayerDatabase, DatasetError> {
+        let probe: VersionProbe = serde_json::from_str(json).map_err(DatasetError::Parse)?;
+        let mut database = match probe.schema_version {
+            SCHEMA_VERSION => serde_json::from_str::<PlayerDatabase>(json).map_err(DatasetError::Parse)?,
+            2 => PlayerDatabase::from(serde_json::from_str::<PlayerDatabaseV2>(json).map_err(DatasetError::Parse)?),
+            version => return Err(DatasetError::UnsupportedVersion(version)),
+        };
+        database.index_player_teams();
+        Ok(database)
+    }
+
+    fn index_player_teams(&mut self) {
+        self.player_teams.clear();
+        for (code, ids) in &self.teams {
+            for id in ids {
+                self.player_teams.entry(id.clone()).or_default().push(code.clone());
+            }
+        }
+    }
+
+    /// Record that a player played for a team. Adding the same player to a team twice is a no-op.
+    pub fn insert(&mut self, team_code: &str, player: PlayerInfo) {
+        let teams = self.player_teams.entry(player.id.clone()).or_default();
+        if !teams.iter().any(|code| code == team_code) {
+            teams.push(team_code.to_string());
+            self.teams.entry(team_code.to_string()).or_default().push(player.id.clone());
+        }
+        self.players.entry(player.id.clone()).or_insert(player);
+    }
+
+    pub fn player(&self, id: &str) -> Option<&PlayerInfo> {
+        self.players.get(id)
+    }
+
+    /// The first player with this name, ignoring case. Names aren't unique, so prefer ids.
+    pub fn player_by_name(&self, name: &str) -> Option<&PlayerInfo> {
+        self.players.values().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Everyone who played for a team, in the order the dataset lists them.
+    pub fn team_players<'a>(&'a self, team_code: &str) -> impl Iterator<Item = &'a PlayerInfo> + 'a {
+        self.teams.get(team_code)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.players.get(id))
+    }
+
+    /// Every team the player with this id played for, as team codes.
+    pub fn teams_for_player(&self, id: &str) -> &[String] {
+        self.player_teams.get(id).map(|codes| codes.as_slice()).unwrap_or_default()
+    }
+
+    /// The dataset in the version 2 shape.
+    pub fn to_v2(&self) -> PlayerDatabaseV2 {
+        PlayerDatabaseV2 {
+            schema_version: 2,
+            teams: self.teams.keys()
+                .map(|code| (code.clone(), self.team_players(code).cloned().collect()))
+                .collect(),
+            generated_at: self.generated_at.clone(),
+            seasons_covered: self.seasons_covered.clone(),
+        }
+    }
+
+    /// The dataset in the original names-only shape.
+    pub fn to_legacy(&self) -> LegacyPlayerDatabase {
+        LegacyPlayerDatabase {
+            teams: self.teams.keys()
+                .map(|code| (code.clone(), self.team_players(code).map(|p| p.name.clone()).collect()))
+                .collect(),
+            generated_at: Some(self.generated_at.clone()),
+            seasons_covered: self.seasons_covered.clone(),
+        }
+    }
+}
+
+impl From<PlayerDatabaseV2> for PlayerDatabase {
+    fn from(v2: PlayerDatabaseV2) -> PlayerDatabase {
+        let mut database = PlayerDatabase::new(v2.generated_at, v2.seasons_covered);
+        for (code, players) in v2.teams {
+            for player in players {
+                database.insert(&code, player);
+            }
+        }
+        database
+    }
+}
+
+/// Key of the split dataset's manifest, relative to its version's prefix (see `registry`).
+/// Publish it last, once every other key is in place.
+pub const MANIFEST_KEY: &str = "manifest";
+
+pub fn player_key(id: &str) -> String {
+    format!("player:{}", id)
+}
+
+pub fn team_key(code: &str) -> String {
+    format!("team:{}", code)
+}
+
+/// A player's `player:{id}` entry: their details and every team they played for.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerEntry {
+    #[serde(flatten)]
+    pub info: PlayerInfo,
+    /// Current team codes
+    pub teams: Vec<String>,
+}
+
+/// A team's `team:{code}` entry: the ids of everyone who played for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEntry {
+    pub code: String,
+    pub players: Vec<String>,
+}
+
+/// Describes the dataset the `player:` and `team:` keys were published from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DatasetManifest {
+    pub schema_version: u32,
+    pub generated_at: String,
+    pub seasons_covered: Vec<String>,
+    pub teams: Vec<String>,
+    pub player_count: usize,
+    /// Pieces the full dataset is split into when it's too big for one KV entry; 0 if it isn't
+    #[serde(default)]
+    pub blob_chunks: u32,
+}
+
+impl PlayerDatabase {
+    /// The player with this id as their `player:{id}` entry.
+    pub fn player_entry(&self, id: &str) -> Option<PlayerEntry> {
+        self.player(id).map(|info| PlayerEntry {
+            info: info.clone(),
+            teams: self.teams_for_player(id).to_vec(),
+        })
+    }
+
+    pub fn manifest(&self) -> DatasetManifest {
+        let teams: Vec<String> = self.teams.keys().cloned().collect();
+        DatasetManifest {
+            schema_version: self.schema_version,
+            generated_at: self.generated_at.clone(),
+            seasons_covered: self.seasons_covered.clone(),
+            teams,
+            player_count: self.players.len(),
+            blob_chunks: 0,
+        }
+    }
+
+    /// The dataset as one KV entry per player and per team, followed by the manifest. Keys are
+    /// relative to a version's prefix.
+    pub fn kv_entries(&self) -> Result<Vec<(String, String)>, serde_json::Error> {
+        let mut entries = Vec::with_capacity(self.players.len() + self.teams.len() + 1);
+        for id in self.players.keys() {
+            if let Some(entry) = self.player_entry(id) {
+                entries.push((player_key(id), serde_json::to_string(&entry)?));
+            }
+        }
+        for (code, players) in &self.teams {
+            let entry = TeamEntry {
+                code: code.clone(),
+                players: players.clone(),
+            };
+            entries.push((team_key(code), serde_json::to_string(&entry)?));
+        }
+        entries.push((MANIFEST_KEY.to_string(), serde_json::to_string(&self.manifest())?));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A version 2 file: Jagr played for both teams, and is listed in full under each
+    const V2: &str = r#"{
+        "schema_version": 2,
+        "teams": {
+            "PIT": [
+                {"id": "8448208", "name": "Jaromir Jagr", "birth_date": "1972-02-15", "birth_place": "Kladno, CZE", "position": "R"},
+                {"id": "8448782", "name": "Mario Lemieux", "birth_date": "1965-10-05", "birth_place": "Montreal, CAN", "position": "C"}
+            ],
+            "NYR": [
+                {"id": "8448208", "name": "Jaromir Jagr", "birth_date": "1972-02-15", "birth_place": "Kladno, CZE", "position": "R"}
+            ]
+        },
+        "generated_at": "2025-10-01T00:00:00Z",
+        "seasons_covered": ["19901991", "20072008"]
+    }"#;
+
+    fn value<T: Serialize>(data: &T) -> serde_json::Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    #[test]
+    fn version_2_upgrades_with_each_player_once() {
+        let database = PlayerDatabase::from_json(V2).unwrap();
+
+        assert_eq!(database.schema_version, SCHEMA_VERSION);
+        assert_eq!(database.players.len(), 2);
+        assert_eq!(database.teams["PIT"], vec!["8448208", "8448782"]);
+        assert_eq!(database.teams["NYR"], vec!["8448208"]);
+        let mut jagr_teams = database.teams_for_player("8448208").to_vec();
+        jagr_teams.sort();
+        assert_eq!(jagr_teams, vec!["NYR", "PIT"]);
+        assert_eq!(database.player("8448782").unwrap().birth_place.as_deref(), Some("Montreal, CAN"));
+    }
+
+    #[test]
+    fn files_without_a_version_are_version_2() {
+        let unversioned = V2.replace(r#""schema_version": 2,"#, "");
+        let database = PlayerDatabase::from_json(&unversioned).unwrap();
+        assert_eq!(value(&database), value(&PlayerDatabase::from_json(V2).unwrap()));
+    }
+
+    #[test]
+    fn converters_round_trip() {
+        let database = PlayerDatabase::from_json(V2).unwrap();
+
+        // v2 -> v3 -> v2 gives back the original file
+        let original: serde_json::Value = serde_json::from_str(V2).unwrap();
+        assert_eq!(value(&database.to_v2()), original);
+
+        // v3 -> JSON -> v3 keeps everything, including the player-to-teams index
+        let reloaded = PlayerDatabase::from_json(&serde_json::to_string(&database).unwrap()).unwrap();
+        assert_eq!(value(&reloaded), value(&database));
+        assert_eq!(reloaded.teams_for_player("8448782"), ["PIT"]);
+
+        // The legacy shape keeps each team's roster order, as names
+        let legacy = database.to_legacy();
+        assert_eq!(legacy.teams["PIT"], vec!["Jaromir Jagr", "Mario Lemieux"]);
+        assert_eq!(legacy.teams["NYR"], vec!["Jaromir Jagr"]);
+        assert_eq!(legacy.generated_at.as_deref(), Some("2025-10-01T00:00:00Z"));
+        assert_eq!(legacy.seasons_covered, database.seasons_covered);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let future = V2.replace(r#""schema_version": 2"#, r#""schema_version": 4"#);
+        assert!(matches!(PlayerDatabase::from_json(&future), Err(DatasetError::UnsupportedVersion(4))));
+    }
+}
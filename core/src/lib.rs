@@ -0,0 +1,11 @@
+//! Game logic shared by the `nhl-player-db` CLI and the Journeyman edge service.
+
+pub mod dataset;
+pub mod ranking;
+pub mod registry;
+pub mod schedule;
+pub mod solver;
+pub mod submission;
+pub mod teams;
+pub mod usage;
+pub mod versioned;
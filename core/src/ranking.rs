@@ -0,0 +1,211 @@
+//! How a day's leaderboard is ordered and what of it is shown.
+//!
+//! Entries are the JSON the edge service keeps for each submission (`board_entry`). Fewer
+//! players ranks higher, then the higher overlap score; submissions that tie on both share a
+//! rank and are listed earliest first. Views of the board never include anyone's user id.
+
+use crate::usage;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Number of entries returned in the "top" view when the client doesn't ask for a limit.
+pub const DEFAULT_TOP_N: usize = 10;
+/// Hard cap on the "top" view so a single request can't ask for the whole board.
+pub const MAX_TOP_N: usize = 100;
+/// How many entries above and below the user to include in the "around me" view.
+pub const AROUND_ME_RADIUS: usize = 2;
+
+/// The part of a submission the board keeps.
+pub fn board_entry(user_id: &str, submission: &Value) -> Value {
+    serde_json::json!({
+        "user_id": user_id,
+        "player_ids": submission["player_ids"],
+        "player_count": submission["player_count"],
+        "overlap_score": submission["overlap_score"],
+        "submitted_at": submission["submitted_at"],
+        "display_name": submission["display_name"]
+    })
+}
+
+/// Fewer players wins; ties are broken by the higher overlap score.
+pub fn compare_scores(a: &Value, b: &Value) -> Ordering {
+    let a_count = a["player_count"].as_u64().unwrap_or(u64::MAX);
+    let b_count = b["player_count"].as_u64().unwrap_or(u64::MAX);
+    let a_overlap = a["overlap_score"].as_f64().unwrap_or(0.0);
+    let b_overlap = b["overlap_score"].as_f64().unwrap_or(0.0);
+
+    a_count.cmp(&b_count).then(b_overlap.total_cmp(&a_overlap))
+}
+
+/// Best first; earlier submissions are listed first within a tie.
+pub fn sort_entries(entries: &mut [Value]) {
+    entries.sort_by(|a, b| {
+        compare_scores(a, b).then_with(|| {
+            let a_time = a["submitted_at"].as_u64().unwrap_or(u64::MAX);
+            let b_time = b["submitted_at"].as_u64().unwrap_or(u64::MAX);
+            a_time.cmp(&b_time)
+        })
+    });
+}
+
+/// Give sorted entries their rank and rarity, and drop the player ids rarity is worked out from.
+pub fn rank(entries: &mut [Value], usage_stats: &HashMap<String, u32>) {
+    // Rarity is relative to everyone who has played so far, so it's recalculated on every read
+    let percentages = usage::pick_percentages(usage_stats, entries.len());
+    for entry in entries.iter_mut() {
+        let player_ids: Vec<String> = entry["player_ids"].as_array()
+            .map(|ids| ids.iter().filter_map(|id| id.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        entry["rarity_score"] = serde_json::json!(usage::rarity_score(&player_ids, &percentages));
+        if let Some(fields) = entry.as_object_mut() {
+            fields.remove("player_ids");
+        }
+    }
+
+    // Tied submissions share the same rank
+    let mut rank = 0;
+    for i in 0..entries.len() {
+        if i == 0 || compare_scores(&entries[i - 1], &entries[i]) != Ordering::Equal {
+            rank = i + 1;
+        }
+        entries[i]["rank"] = serde_json::json!(rank);
+    }
+}
+
+/// Where a submission ranks among `entries`: one more than the number of strictly better ones.
+pub fn position(submission: &Value, entries: &[Value]) -> u32 {
+    let better = entries.iter()
+        .filter(|other| compare_scores(other, submission) == Ordering::Less)
+        .count();
+    better as u32 + 1
+}
+
+// An entry as anyone may see it: who submitted it is only shown by display name
+fn public_entry(entry: &Value) -> Value {
+    let mut entry = entry.clone();
+    if let Some(fields) = entry.as_object_mut() {
+        fields.remove("user_id");
+    }
+    entry
+}
+
+/// The top-N and "around me" views of a ranked board (`{date, puzzle_number, submissions}`).
+///
+/// `attach_names` is given the entries that will be shown, still with their user ids, to fill in
+/// display names; the ids are removed afterwards.
+pub fn view<E>(
+    leaderboard: &Value,
+    top_n: usize,
+    user_id: Option<&str>,
+    mut attach_names: impl FnMut(&mut [Value]) -> Result<(), E>,
+) -> Result<Value, E> {
+    let empty = Vec::new();
+    let submissions = leaderboard["submissions"].as_array().unwrap_or(&empty);
+
+    let mut top: Vec<Value> = submissions.iter().take(top_n.min(MAX_TOP_N)).cloned().collect();
+
+    let my_index = user_id.and_then(|id| submissions.iter().position(|s| s["user_id"].as_str() == Some(id)));
+    // The entries around the user's, and where theirs is among them
+    let mut around_me = my_index.map(|index| {
+        let start = index.saturating_sub(AROUND_ME_RADIUS);
+        let end = (index + AROUND_ME_RADIUS + 1).min(submissions.len());
+        (index - start, submissions[start..end].to_vec())
+    });
+
+    attach_names(&mut top)?;
+    if let Some((_, entries)) = around_me.as_mut() {
+        attach_names(entries)?;
+    }
+    let public = |entries: &[Value]| entries.iter().map(public_entry).collect::<Vec<Value>>();
+
+    Ok(serde_json::json!({
+        "date": leaderboard["date"],
+        "puzzle_number": leaderboard["puzzle_number"],
+        "total_submissions": submissions.len(),
+        "top": public(&top),
+        "me": around_me.as_ref().map(|(mine, entries)| public_entry(&entries[*mine])),
+        "around_me": around_me.as_ref().map(|(_, entries)| public(entries))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    fn entry(user_id: &str, player_count: u64, overlap_score: f64, submitted_at: u64) -> Value {
+        let submission = serde_json::json!({
+            "player_ids": [format!("p{}", user_id)],
+            "player_count": player_count,
+            "overlap_score": overlap_score,
+            "submitted_at": submitted_at,
+            "display_name": null
+        });
+        board_entry(user_id, &submission)
+    }
+
+    // A sorted, ranked board of these entries
+    fn board(mut entries: Vec<Value>) -> Value {
+        sort_entries(&mut entries);
+        rank(&mut entries, &HashMap::new());
+        serde_json::json!({ "date": "2025-10-01", "puzzle_number": 1, "submissions": entries })
+    }
+
+    fn users(entries: &Value) -> Vec<&str> {
+        entries.as_array().unwrap().iter().map(|entry| entry["user_id"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn fewer_players_then_higher_overlap_then_earlier() {
+        let board = board(vec![
+            entry("late_tie", 4, 2.0, 300),
+            entry("five", 5, 9.0, 100),
+            entry("low_overlap", 4, 1.0, 50),
+            entry("early_tie", 4, 2.0, 200),
+            entry("three", 3, 0.0, 400),
+        ]);
+        let submissions = &board["submissions"];
+        assert_eq!(users(submissions), vec!["three", "early_tie", "late_tie", "low_overlap", "five"]);
+
+        let ranks: Vec<u64> = submissions.as_array().unwrap().iter().map(|entry| entry["rank"].as_u64().unwrap()).collect();
+        assert_eq!(ranks, vec![1, 2, 2, 4, 5]);
+        assert!(submissions.as_array().unwrap().iter().all(|entry| entry.get("player_ids").is_none()));
+    }
+
+    #[test]
+    fn position_counts_only_strictly_better_entries() {
+        let entries = vec![entry("a", 3, 1.0, 1), entry("b", 4, 2.0, 2), entry("c", 4, 1.0, 3)];
+        assert_eq!(position(&entry("me", 4, 2.0, 9), &entries), 2);
+        assert_eq!(position(&entry("me", 2, 0.0, 9), &entries), 1);
+        assert_eq!(position(&entry("me", 6, 0.0, 9), &entries), 4);
+    }
+
+    #[test]
+    fn views_never_show_user_ids() {
+        let board = board((0..10).map(|i| entry(&format!("user{}", i), 3 + i, 0.0, i)).collect());
+        let view = view(&board, 3, Some("user6"), |entries: &mut [Value]| {
+            for entry in entries {
+                entry["display_name"] = serde_json::json!(format!("Name {}", entry["user_id"].as_str().unwrap()));
+            }
+            Ok::<_, Infallible>(())
+        })
+        .unwrap();
+
+        assert_eq!(view["total_submissions"], 10);
+        assert_eq!(view["top"].as_array().unwrap().len(), 3);
+        assert_eq!(view["me"]["rank"], 7);
+        assert_eq!(view["me"]["display_name"], "Name user6");
+        let around: Vec<u64> = view["around_me"].as_array().unwrap().iter().map(|entry| entry["rank"].as_u64().unwrap()).collect();
+        assert_eq!(around, vec![5, 6, 7, 8, 9]);
+        assert!(!view.to_string().contains("\"user_id\""));
+    }
+
+    #[test]
+    fn view_without_a_submission_has_no_me() {
+        let board = board(vec![entry("a", 3, 0.0, 1)]);
+        let view = view(&board, 500, Some("someone_else"), |_: &mut [Value]| Ok::<_, Infallible>(())).unwrap();
+        assert!(view["me"].is_null() && view["around_me"].is_null());
+        assert_eq!(view["top"].as_array().unwrap().len(), 1);
+    }
+}
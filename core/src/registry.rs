@@ -0,0 +1,241 @@
+//! The dataset registry: every published dataset version, where its keys live, and which one the
+//! edge service is serving.
+//!
+//! Each version's keys share a prefix (`dataset:{version}:player:{id}` and so on, with the full
+//! dataset under `dataset:{version}`), so uploading a new version never touches the live one.
+//! A full dataset too big for one KV entry is stored in pieces under `dataset:{version}:blob:{n}`
+//! instead, and its manifest says how many there are.
+//! The registry is a single KV entry, which makes promoting a version or rolling back to the
+//! previous one a single write.
+
+use crate::dataset::DatasetManifest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Key the registry is stored under.
+pub const REGISTRY_KEY: &str = "dataset_registry";
+
+/// Largest full dataset stored as a single entry; Fastly's KV values are limited to 25 MB.
+pub const MAX_BLOB_BYTES: usize = 20 * 1024 * 1024;
+
+/// Where one published version lives and what it contains.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatasetVersion {
+    pub schema_version: u32,
+    pub generated_at: String,
+    pub registered_at: String,
+    pub player_count: usize,
+    /// Key of the full dataset
+    pub blob_key: String,
+    /// Prepended to the split entries' keys
+    pub key_prefix: String,
+    /// Pieces the full dataset is stored in (see `blob_chunk_key`); 0 if it's all under `blob_key`
+    #[serde(default)]
+    pub blob_chunks: u32,
+}
+
+impl DatasetVersion {
+    /// The registry entry for a version uploaded under the standard keys.
+    pub fn new(version: u32, manifest: &DatasetManifest, registered_at: String) -> DatasetVersion {
+        DatasetVersion {
+            schema_version: manifest.schema_version,
+            generated_at: manifest.generated_at.clone(),
+            registered_at,
+            player_count: manifest.player_count,
+            blob_key: DatasetVersion::blob_key_for(version),
+            key_prefix: DatasetVersion::key_prefix_for(version),
+            blob_chunks: manifest.blob_chunks,
+        }
+    }
+
+    /// Key of the full dataset for a version number.
+    pub fn blob_key_for(version: u32) -> String {
+        format!("dataset:{}", version)
+    }
+
+    /// Prefix of the split entries for a version number.
+    pub fn key_prefix_for(version: u32) -> String {
+        format!("dataset:{}:", version)
+    }
+
+    /// The full key of one of this version's split entries, e.g. `key(&player_key(id))`.
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DatasetRegistry {
+    pub current: Option<u32>,
+    /// Versions that were current before, most recent last
+    #[serde(default)]
+    pub previous: Vec<u32>,
+    pub versions: BTreeMap<u32, DatasetVersion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    UnknownVersion(u32),
+    VersionExists(u32),
+    AlreadyCurrent(u32),
+    NothingToRollBack,
+    /// The version's entries aren't all uploaded, or are for another schema
+    Incomplete(u32),
+}
+
+impl RegistryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RegistryError::UnknownVersion(_) => "unknown_version",
+            RegistryError::VersionExists(_) => "version_exists",
+            RegistryError::AlreadyCurrent(_) => "already_current",
+            RegistryError::NothingToRollBack => "nothing_to_roll_back",
+            RegistryError::Incomplete(_) => "incomplete_version",
+        }
+    }
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::UnknownVersion(version) => write!(f, "dataset version {} is not registered", version),
+            RegistryError::VersionExists(version) => write!(f, "dataset version {} is already registered", version),
+            RegistryError::AlreadyCurrent(version) => write!(f, "dataset version {} is already current", version),
+            RegistryError::NothingToRollBack => write!(f, "no earlier dataset version to roll back to"),
+            RegistryError::Incomplete(version) => {
+                write!(f, "dataset version {} is missing its manifest or has an unsupported schema", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl DatasetRegistry {
+    pub fn get(&self, version: u32) -> Option<&DatasetVersion> {
+        self.versions.get(&version)
+    }
+
+    pub fn current_version(&self) -> Option<(u32, &DatasetVersion)> {
+        self.current.and_then(|version| self.get(version).map(|entry| (version, entry)))
+    }
+
+    /// One more than the highest version registered so far.
+    pub fn next_version(&self) -> u32 {
+        self.versions.keys().next_back().map_or(1, |version| version + 1)
+    }
+
+    pub fn register(&mut self, version: u32, entry: DatasetVersion) -> Result<(), RegistryError> {
+        if self.versions.contains_key(&version) {
+            return Err(RegistryError::VersionExists(version));
+        }
+        self.versions.insert(version, entry);
+        Ok(())
+    }
+
+    /// Make `version` current, remembering the version it replaces for `rollback`.
+    pub fn promote(&mut self, version: u32) -> Result<(), RegistryError> {
+        if !self.versions.contains_key(&version) {
+            return Err(RegistryError::UnknownVersion(version));
+        }
+        if self.current == Some(version) {
+            return Err(RegistryError::AlreadyCurrent(version));
+        }
+        if let Some(current) = self.current {
+            self.previous.push(current);
+        }
+        self.current = Some(version);
+        Ok(())
+    }
+
+    /// Go back to the version that was current before this one. Returns the new current version.
+    pub fn rollback(&mut self) -> Result<u32, RegistryError> {
+        while let Some(version) = self.previous.pop() {
+            if self.versions.contains_key(&version) {
+                self.current = Some(version);
+                return Ok(version);
+            }
+        }
+        Err(RegistryError::NothingToRollBack)
+    }
+}
+
+/// Key of one piece of a full dataset stored in pieces, relative to its version's prefix.
+pub fn blob_chunk_key(index: u32) -> String {
+    format!("blob:{}", index)
+}
+
+/// Split a serialized dataset into pieces of at most `max_bytes`, in order. Pieces end on
+/// character boundaries so each one is valid UTF-8 on its own.
+pub fn split_blob(blob: &str, max_bytes: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = blob;
+    while !rest.is_empty() {
+        let mut end = max_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, remainder) = rest.split_at(end);
+        pieces.push(piece);
+        rest = remainder;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(number: u32) -> DatasetVersion {
+        DatasetVersion {
+            schema_version: 3,
+            generated_at: "2025-10-01T00:00:00Z".to_string(),
+            registered_at: "2025-10-01T00:00:00Z".to_string(),
+            player_count: 0,
+            blob_key: DatasetVersion::blob_key_for(number),
+            key_prefix: DatasetVersion::key_prefix_for(number),
+            blob_chunks: 0,
+        }
+    }
+
+    fn registry(versions: u32) -> DatasetRegistry {
+        let mut registry = DatasetRegistry::default();
+        for number in 1..=versions {
+            registry.register(number, version(number)).unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn rollback_walks_back_through_promotions() {
+        let mut registry = registry(3);
+        registry.promote(1).unwrap();
+        registry.promote(3).unwrap();
+        registry.promote(2).unwrap();
+
+        assert_eq!(registry.rollback(), Ok(3));
+        assert_eq!(registry.rollback(), Ok(1));
+        assert_eq!(registry.rollback(), Err(RegistryError::NothingToRollBack));
+        assert_eq!(registry.current, Some(1));
+    }
+
+    #[test]
+    fn promote_rejects_unknown_and_current_versions() {
+        let mut registry = registry(2);
+        assert_eq!(registry.promote(5), Err(RegistryError::UnknownVersion(5)));
+        registry.promote(2).unwrap();
+        assert_eq!(registry.promote(2), Err(RegistryError::AlreadyCurrent(2)));
+        assert_eq!(registry.next_version(), 3);
+        assert_eq!(registry.register(2, version(2)), Err(RegistryError::VersionExists(2)));
+    }
+
+    #[test]
+    fn split_blob_keeps_characters_whole() {
+        let blob = r#"{"name":"Tomáš Hertl","birth_place":"Praha, CZE"}"#;
+        let pieces = split_blob(blob, 13);
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|piece| !piece.is_empty() && piece.len() <= 13));
+        assert_eq!(pieces.concat(), blob);
+        assert_eq!(split_blob(blob, blob.len()), vec![blob]);
+    }
+}
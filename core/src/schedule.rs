@@ -0,0 +1,171 @@
+//! Keyed daily puzzle schedule.
+//!
+//! Candidate team sets come from an HMAC-SHA256 keyed shuffle of the team pool, so without the
+//! secret salt nobody can work out future puzzles from past ones. `ScheduleHistory` remembers
+//! which sets were used recently so a set can't come back inside the configured window.
+//!
+//! Before the keyed schedule, each day's teams came from a plain LCG seeded with the day.
+//! `legacy_teams` reproduces it so those days can still be replayed as they were played.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Order-independent identity of a team set, used to detect repeats.
+pub fn set_key(teams: &[&str]) -> String {
+    let mut sorted = teams.to_vec();
+    sorted.sort_unstable();
+    sorted.join("|")
+}
+
+/// Candidate number `attempt` for `day`: the first `size` teams of the pool after sorting it by a
+/// keyed hash of (day, attempt, team). Each attempt is an independent shuffle.
+pub fn candidate<'a>(salt: &[u8], pool: &[&'a str], day: u64, attempt: u32, size: usize) -> Vec<&'a str> {
+    let mut keyed: Vec<([u8; 32], &'a str)> = pool.iter()
+        .map(|&team| {
+            let mut mac = HmacSha256::new_from_slice(salt).expect("HMAC accepts keys of any length");
+            mac.update(format!("{}:{}:{}", day, attempt, team).as_bytes());
+            (mac.finalize().into_bytes().into(), team)
+        })
+        .collect();
+    keyed.sort_unstable();
+    keyed.into_iter().take(size).map(|(_, team)| team).collect()
+}
+
+// The team pool the original generator drew from, in its order
+const LEGACY_POOL: [&str; 32] = [
+    "Anaheim Ducks", "Boston Bruins", "Buffalo Sabres", "Calgary Flames",
+    "Carolina Hurricanes", "Chicago Blackhawks", "Colorado Avalanche",
+    "Columbus Blue Jackets", "Dallas Stars", "Detroit Red Wings",
+    "Edmonton Oilers", "Florida Panthers", "Los Angeles Kings",
+    "Minnesota Wild", "Montreal Canadiens", "Nashville Predators",
+    "New Jersey Devils", "New York Islanders", "New York Rangers",
+    "Ottawa Senators", "Philadelphia Flyers", "Pittsburgh Penguins",
+    "San Jose Sharks", "Seattle Kraken", "St. Louis Blues",
+    "Tampa Bay Lightning", "Toronto Maple Leafs", "Utah Hockey Club",
+    "Vancouver Canucks", "Vegas Golden Knights", "Washington Capitals",
+    "Winnipeg Jets",
+];
+const LEGACY_TEAMS_PER_PUZZLE: usize = 8;
+
+/// The teams the original generator picked for `day`.
+pub fn legacy_teams(day: u64) -> Vec<&'static str> {
+    let mut available = LEGACY_POOL.to_vec();
+    let mut seed = day;
+    let mut teams = Vec::with_capacity(LEGACY_TEAMS_PER_PUZZLE);
+    for _ in 0..LEGACY_TEAMS_PER_PUZZLE {
+        teams.push(available.remove((seed % available.len() as u64) as usize));
+        // Only the low 31 bits survive, so this matches the wrapping 32-bit arithmetic it ran with
+        seed = (seed.wrapping_mul(1103515245).wrapping_add(12345)) % (1 << 31);
+    }
+    teams
+}
+
+/// The day each recently used team set was last scheduled.
+#[derive(Debug, Clone)]
+pub struct ScheduleHistory {
+    window_days: u64,
+    last_used: HashMap<String, u64>,
+}
+
+impl ScheduleHistory {
+    pub fn new(window_days: u64) -> Self {
+        ScheduleHistory {
+            window_days,
+            last_used: HashMap::new(),
+        }
+    }
+
+    /// Restore a history from `(set_key, day)` pairs, e.g. as saved by `entries`.
+    pub fn with_entries(window_days: u64, entries: impl IntoIterator<Item = (String, u64)>) -> Self {
+        ScheduleHistory {
+            window_days,
+            last_used: entries.into_iter().collect(),
+        }
+    }
+
+    /// True if the same set was scheduled on another day within the window of `day`.
+    pub fn is_repeat(&self, teams: &[&str], day: u64) -> bool {
+        self.last_used.get(&set_key(teams))
+            .is_some_and(|&used| used != day && used.abs_diff(day) < self.window_days)
+    }
+
+    /// Remember that `teams` was scheduled on `day` and forget sets that have aged out.
+    pub fn record(&mut self, teams: &[&str], day: u64) {
+        let window_days = self.window_days;
+        self.last_used.retain(|_, used| used.abs_diff(day) < window_days);
+        self.last_used.insert(set_key(teams), day);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.last_used.iter().map(|(key, day)| (key.as_str(), *day))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const NHL_TEAMS: [&str; 32] = [
+        "ANA", "BOS", "BUF", "CGY", "CAR", "CHI", "COL", "CBJ", "DAL", "DET", "EDM", "FLA", "LAK",
+        "MIN", "MTL", "NSH", "NJD", "NYI", "NYR", "OTT", "PHI", "PIT", "SJS", "SEA", "STL", "TBL",
+        "TOR", "UTA", "VAN", "VGK", "WSH", "WPG",
+    ];
+
+    // Take the first candidate for each day that isn't a repeat, like the edge service does
+    fn schedule(salt: &[u8], pool: &[&'static str], size: usize, days: u64, window_days: u64) -> Vec<Vec<&'static str>> {
+        let mut history = ScheduleHistory::new(window_days);
+        (20_000..20_000 + days)
+            .map(|day| {
+                let teams = (0..)
+                    .map(|attempt| candidate(salt, pool, day, attempt, size))
+                    .find(|teams| !history.is_repeat(teams, day))
+                    .unwrap();
+                history.record(&teams, day);
+                teams
+            })
+            .collect()
+    }
+
+    #[test]
+    fn year_of_schedules_has_no_repeats() {
+        let year = schedule(b"test-salt", &NHL_TEAMS, 8, 365, 365);
+        let distinct: HashSet<String> = year.iter().map(|teams| set_key(teams)).collect();
+        assert_eq!(distinct.len(), 365);
+        assert!(year.iter().all(|teams| teams.len() == 8 && set_key(teams).split('|').collect::<HashSet<_>>().len() == 8));
+    }
+
+    #[test]
+    fn small_pool_waits_out_the_window() {
+        // Only 20 possible sets, so collisions happen constantly and the history has to reroll them
+        let pool = ["ANA", "BOS", "BUF", "CGY", "CAR", "CHI"];
+        let window_days = 15;
+        let days = schedule(b"test-salt", &pool, 3, 365, window_days);
+        for (i, teams) in days.iter().enumerate() {
+            let start = i.saturating_sub(window_days as usize - 1);
+            for earlier in &days[start..i] {
+                assert_ne!(set_key(teams), set_key(earlier), "day {} repeats within the window", i);
+            }
+        }
+    }
+
+    #[test]
+    fn schedule_depends_on_the_salt() {
+        let a = schedule(b"salt-a", &NHL_TEAMS, 8, 30, 365);
+        let b = schedule(b"salt-b", &NHL_TEAMS, 8, 30, 365);
+        assert_eq!(a, schedule(b"salt-a", &NHL_TEAMS, 8, 30, 365));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn legacy_teams_match_what_was_played() {
+        // 2025-09-15, the first puzzle
+        assert_eq!(legacy_teams(20346), vec![
+            "Toronto Maple Leafs", "Los Angeles Kings", "Minnesota Wild", "St. Louis Blues",
+            "Buffalo Sabres", "San Jose Sharks", "Tampa Bay Lightning", "Nashville Predators",
+        ]);
+    }
+}
@@ -0,0 +1,329 @@
+//! Exact minimum set cover for a puzzle: the fewest players who together played for every team.
+//!
+//! Each player becomes a bitmask of the puzzle teams they played for. `min_needed[s]` is the fewest
+//! players whose teams cover every team in the set `s`, filled in by dynamic programming over all
+//! 2^n team subsets. Optimal covers are then enumerated by walking down from the full set, only
+//! taking steps that keep the remaining subset solvable in the remaining number of picks.
+//!
+//! A puzzle's difficulty comes from the solution: how many players it needs, and how many ways
+//! there are to do it in that many.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Largest puzzle the solver accepts; the DP table has 2^MAX_TEAMS entries.
+pub const MAX_TEAMS: usize = 16;
+
+// Stop enumerating after this many ordered pick sequences; beyond that counts are a lower bound
+const MAX_ENUMERATED_PATHS: usize = 200_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    NoTeams,
+    TooManyTeams(usize),
+    /// Index of a team nobody in the dataset played for
+    UncoverableTeam(usize),
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::NoTeams => write!(f, "no teams to cover"),
+            SolveError::TooManyTeams(count) => {
+                write!(f, "{} teams is more than the solver supports ({})", count, MAX_TEAMS)
+            }
+            SolveError::UncoverableTeam(index) => write!(f, "team #{} has no players", index + 1),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+#[derive(Debug, Clone)]
+pub struct CoverSolution {
+    /// Fewest players that cover every team
+    pub min_players: usize,
+    /// Number of distinct player sets of that size that cover every team
+    pub optimal_covers: u64,
+    /// False if enumeration hit its limit, in which case `optimal_covers` is a lower bound
+    pub exhaustive: bool,
+    /// Some optimal covers, as player ids with one player per pick
+    pub samples: Vec<Vec<String>>,
+}
+
+/// Solve for `team_players[i]` = ids of everyone who played for team `i`.
+pub fn solve(team_players: &[Vec<String>], max_samples: usize) -> Result<CoverSolution, SolveError> {
+    let team_count = team_players.len();
+    if team_count == 0 {
+        return Err(SolveError::NoTeams);
+    }
+    if team_count > MAX_TEAMS {
+        return Err(SolveError::TooManyTeams(team_count));
+    }
+    if let Some(index) = team_players.iter().position(|players| players.is_empty()) {
+        return Err(SolveError::UncoverableTeam(index));
+    }
+
+    // Which puzzle teams each player covers
+    let mut player_masks: HashMap<&str, u32> = HashMap::new();
+    for (team, players) in team_players.iter().enumerate() {
+        for player in players {
+            *player_masks.entry(player.as_str()).or_insert(0) |= 1 << team;
+        }
+    }
+
+    // Players with identical coverage are interchangeable, so search over distinct masks
+    let mut players_by_mask: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+    for (player, mask) in player_masks {
+        players_by_mask.entry(mask).or_default().push(player);
+    }
+    for players in players_by_mask.values_mut() {
+        players.sort_unstable();
+    }
+    let masks: Vec<u32> = players_by_mask.keys().copied().collect();
+
+    let min_needed = min_players_table(team_count, &masks);
+    let full = (1u32 << team_count) - 1;
+    let min_players = min_needed[full as usize] as usize;
+
+    let mut enumeration = Enumeration {
+        min_needed: &min_needed,
+        masks: &masks,
+        chosen: Vec::with_capacity(min_players),
+        covers: BTreeSet::new(),
+        paths: 0,
+    };
+    enumeration.walk(full, min_players);
+    let exhaustive = enumeration.paths < MAX_ENUMERATED_PATHS;
+
+    let optimal_covers = enumeration.covers.iter()
+        .map(|cover| {
+            cover.iter()
+                .map(|mask| players_by_mask[mask].len() as u64)
+                .fold(1u64, |total, choices| total.saturating_mul(choices))
+        })
+        .fold(0u64, |total, count| total.saturating_add(count));
+
+    let samples = enumeration.covers.iter()
+        .take(max_samples)
+        .map(|cover| cover.iter().map(|mask| players_by_mask[mask][0].to_string()).collect())
+        .collect();
+
+    Ok(CoverSolution {
+        min_players,
+        optimal_covers,
+        exhaustive,
+        samples,
+    })
+}
+
+// min_needed[s] for every subset s of the teams
+fn min_players_table(team_count: usize, masks: &[u32]) -> Vec<u8> {
+    // A mask contained in another never helps reach the minimum, so drop it for the DP
+    let useful: Vec<u32> = masks.iter()
+        .copied()
+        .filter(|&mask| !masks.iter().any(|&other| other != mask && other & mask == mask))
+        .collect();
+
+    let size = 1usize << team_count;
+    let mut min_needed = vec![u8::MAX; size];
+    min_needed[0] = 0;
+    for subset in 1..size as u32 {
+        // Some pick has to cover the lowest remaining team, so only branch on those
+        let lowest = subset.isolate_lowest_one();
+        let best = useful.iter()
+            .filter(|&&mask| mask & lowest != 0)
+            .map(|&mask| min_needed[(subset & !mask) as usize])
+            .min()
+            .unwrap_or(u8::MAX);
+        min_needed[subset as usize] = best.saturating_add(1);
+    }
+    min_needed
+}
+
+struct Enumeration<'a> {
+    min_needed: &'a [u8],
+    masks: &'a [u32],
+    chosen: Vec<u32>,
+    covers: BTreeSet<Vec<u32>>,
+    paths: usize,
+}
+
+impl Enumeration<'_> {
+    fn walk(&mut self, remaining: u32, picks_left: usize) {
+        if self.paths >= MAX_ENUMERATED_PATHS {
+            return;
+        }
+        if remaining == 0 {
+            self.paths += 1;
+            let mut cover = self.chosen.clone();
+            cover.sort_unstable();
+            self.covers.insert(cover);
+            return;
+        }
+
+        let lowest = remaining.isolate_lowest_one();
+        for &mask in self.masks {
+            if mask & lowest == 0 {
+                continue;
+            }
+            let rest = remaining & !mask;
+            if self.min_needed[rest as usize] as usize + 1 != picks_left {
+                continue;
+            }
+            self.chosen.push(mask);
+            self.walk(rest, picks_left - 1);
+            self.chosen.pop();
+        }
+    }
+}
+
+/// Acceptable range (inclusive) for each difficulty metric, e.g.
+/// `{"min_players": [2, 3], "optimal_covers": [2, 100]}`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyBand {
+    pub min_players: (usize, usize),
+    pub optimal_covers: (u64, u64),
+}
+
+impl Default for DifficultyBand {
+    fn default() -> Self {
+        DifficultyBand {
+            min_players: (2, 3),
+            optimal_covers: (2, 100),
+        }
+    }
+}
+
+impl DifficultyBand {
+    /// How far a solution is from the band; 0 means it's inside.
+    pub fn distance(&self, min_players: usize, optimal_covers: u64) -> u64 {
+        let players_off = if min_players < self.min_players.0 {
+            self.min_players.0 - min_players
+        } else {
+            min_players.saturating_sub(self.min_players.1)
+        };
+        let covers_off = if optimal_covers < self.optimal_covers.0 {
+            self.optimal_covers.0 - optimal_covers
+        } else {
+            optimal_covers.saturating_sub(self.optimal_covers.1)
+        };
+        // A whole extra player matters far more than a few more or fewer optimal answers
+        players_off as u64 * 1000 + covers_off.min(999)
+    }
+}
+
+/// Fewer players needed and more ways to get there both make a puzzle easier.
+pub fn difficulty_label(min_players: usize, optimal_covers: u64) -> &'static str {
+    match (min_players, optimal_covers) {
+        (0..=2, 10..) => "easy",
+        (0..=2, _) | (3, 10..) => "medium",
+        (3, _) => "hard",
+        _ => "expert",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(players: &[&str]) -> Vec<String> {
+        players.iter().map(|player| player.to_string()).collect()
+    }
+
+    // Every team has at least one player from the cover
+    fn covers_all(team_players: &[Vec<String>], cover: &[String]) -> bool {
+        team_players.iter().all(|players| players.iter().any(|player| cover.contains(player)))
+    }
+
+    #[test]
+    fn finds_the_minimum_and_counts_interchangeable_players() {
+        // "a" and "b" both played for teams 0 and 1, "c" for 2 and 3, "d" for 1 and 2
+        let team_players = vec![
+            ids(&["a", "b"]),
+            ids(&["a", "b", "d"]),
+            ids(&["c", "d"]),
+            ids(&["c"]),
+        ];
+        let solution = solve(&team_players, 10).unwrap();
+
+        assert_eq!(solution.min_players, 2);
+        // {a, c} and {b, c}; "d" never helps reach two
+        assert_eq!(solution.optimal_covers, 2);
+        assert!(solution.exhaustive);
+        assert_eq!(solution.samples, vec![ids(&["a", "c"])]);
+    }
+
+    #[test]
+    fn samples_are_optimal_covers() {
+        let team_players = vec![
+            ids(&["a", "e"]),
+            ids(&["b", "e"]),
+            ids(&["c", "f"]),
+            ids(&["d", "f"]),
+            ids(&["a", "b", "c", "d"]),
+        ];
+        let solution = solve(&team_players, 10).unwrap();
+
+        // "e" and "f" with any of "a" to "d", or "a", "b" and "f", or "c", "d" and "e"
+        assert_eq!(solution.min_players, 3);
+        assert_eq!(solution.optimal_covers, 6);
+        assert_eq!(solution.samples.len(), 6);
+        for sample in &solution.samples {
+            assert_eq!(sample.len(), solution.min_players);
+            assert!(covers_all(&team_players, sample), "{:?} misses a team", sample);
+        }
+    }
+
+    #[test]
+    fn stops_enumerating_at_the_limit() {
+        // One player for every proper subset of 12 teams: two picks are needed, and there are
+        // far more ways to pick them than the enumeration limit
+        let teams = 12;
+        let full = (1u32 << teams) - 1;
+        let team_players: Vec<Vec<String>> = (0..teams)
+            .map(|team| (1..full).filter(|mask| mask & (1 << team) != 0).map(|mask| format!("p{}", mask)).collect())
+            .collect();
+        let solution = solve(&team_players, 5).unwrap();
+
+        assert_eq!(solution.min_players, 2);
+        assert!(!solution.exhaustive);
+        assert!(solution.optimal_covers > 0);
+        assert_eq!(solution.samples.len(), 5);
+        for sample in &solution.samples {
+            assert!(covers_all(&team_players, sample));
+        }
+    }
+
+    #[test]
+    fn rejects_unsolvable_puzzles() {
+        assert_eq!(solve(&[], 0).unwrap_err(), SolveError::NoTeams);
+        assert_eq!(solve(&vec![ids(&["a"]); MAX_TEAMS + 1], 0).unwrap_err(), SolveError::TooManyTeams(MAX_TEAMS + 1));
+        assert_eq!(solve(&[ids(&["a"]), Vec::new()], 0).unwrap_err(), SolveError::UncoverableTeam(1));
+    }
+
+    #[test]
+    fn distance_is_zero_inside_the_band_and_weighs_players_first() {
+        let band = DifficultyBand::default();
+        assert_eq!(band.distance(2, 2), 0);
+        assert_eq!(band.distance(3, 100), 0);
+        assert_eq!(band.distance(1, 50), 1000);
+        assert_eq!(band.distance(3, 101), 1);
+        assert_eq!(band.distance(2, 1), 1);
+        // However far off the cover count is, one player off is worse
+        assert!(band.distance(2, 1_000_000) < band.distance(4, 50));
+
+        let configured: DifficultyBand = serde_json::from_str(r#"{"min_players": [3, 4], "optimal_covers": [1, 10]}"#).unwrap();
+        assert_eq!(configured.distance(4, 10), 0);
+        assert_eq!(configured.distance(2, 5), 1000);
+    }
+
+    #[test]
+    fn labels_get_harder_with_more_players_and_fewer_answers() {
+        assert_eq!(difficulty_label(2, 10), "easy");
+        assert_eq!(difficulty_label(2, 9), "medium");
+        assert_eq!(difficulty_label(3, 10), "medium");
+        assert_eq!(difficulty_label(3, 9), "hard");
+        assert_eq!(difficulty_label(4, 1000), "expert");
+    }
+}
@@ -0,0 +1,226 @@
+//! The rules a daily submission has to pass, so a hand-crafted POST can't land on the leaderboard.
+//!
+//! The edge service checks the date and supplies the lookups into its dataset; everything else
+//! about whether a list of players answers a puzzle is decided here.
+
+use crate::teams;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A submitted player matched to exactly one entry in the dataset.
+pub struct ResolvedPlayer {
+    pub id: String,
+    pub name: String,
+    /// Current team codes
+    pub teams: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SubmissionError {
+    InvalidDate(String),
+    WrongDate { submitted: String, expected: String },
+    NotInArchive(String),
+    NoPlayers,
+    TooManyPlayers(usize),
+    InvalidPlayer,
+    UnknownPlayer(String),
+    AmbiguousPlayer { name: String, ids: Vec<String> },
+    DuplicatePlayer(String),
+    MissingTeams(Vec<String>),
+}
+
+impl SubmissionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubmissionError::InvalidDate(_) => "invalid_date",
+            SubmissionError::WrongDate { .. } => "wrong_date",
+            SubmissionError::NotInArchive(_) => "not_in_archive",
+            SubmissionError::NoPlayers => "no_players",
+            SubmissionError::TooManyPlayers(_) => "too_many_players",
+            SubmissionError::InvalidPlayer => "invalid_player",
+            SubmissionError::UnknownPlayer(_) => "unknown_player",
+            SubmissionError::AmbiguousPlayer { .. } => "ambiguous_player",
+            SubmissionError::DuplicatePlayer(_) => "duplicate_player",
+            SubmissionError::MissingTeams(_) => "missing_teams",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            SubmissionError::InvalidDate(date) => format!("'{}' is not a valid puzzle date", date),
+            SubmissionError::WrongDate { submitted, expected } => {
+                format!("Submissions are only accepted for today's puzzle ({}), not {}", expected, submitted)
+            }
+            SubmissionError::NotInArchive(date) => format!("{} is not a past puzzle", date),
+            SubmissionError::NoPlayers => "A submission needs at least one player".to_string(),
+            SubmissionError::TooManyPlayers(max) => {
+                format!("A submission can have at most {} players, one per team", max)
+            }
+            SubmissionError::InvalidPlayer => "Players must be names or objects with a name or id".to_string(),
+            SubmissionError::UnknownPlayer(player) => format!("'{}' is not in the player database", player),
+            SubmissionError::AmbiguousPlayer { name, ids } => {
+                format!("'{}' matches {} players; submit an id instead", name, ids.len())
+            }
+            SubmissionError::DuplicatePlayer(player) => format!("'{}' was submitted more than once", player),
+            SubmissionError::MissingTeams(teams) => {
+                format!("The submitted players don't cover: {}", teams.join(", "))
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut json = serde_json::json!({
+            "error": self.code(),
+            "message": self.message()
+        });
+        match self {
+            SubmissionError::WrongDate { expected, .. } => json["expected_date"] = serde_json::json!(expected),
+            SubmissionError::TooManyPlayers(max) => json["max_players"] = serde_json::json!(max),
+            SubmissionError::AmbiguousPlayer { ids, .. } => json["candidate_ids"] = serde_json::json!(ids),
+            SubmissionError::MissingTeams(teams) => json["missing_teams"] = serde_json::json!(teams),
+            _ => {}
+        }
+        json
+    }
+}
+
+/// Most players a submission for these teams may list. One player per team covers any puzzle,
+/// so a longer list can't be a better answer, and every player costs a dataset lookup.
+pub fn max_players(puzzle_teams: &[String]) -> usize {
+    puzzle_teams.len()
+}
+
+/// Resolve submitted players and confirm they cover every one of the puzzle's teams.
+///
+/// Players are plain names (older clients) or `{name, id}` objects. `find` returns every dataset
+/// entry matching an id, or failing that a name; its errors are passed back as the outer error,
+/// and the inner one is a rejected submission. A list that's too long is refused before anything
+/// is looked up.
+pub fn validate_players<E>(
+    players: &[Value],
+    puzzle_teams: &[String],
+    mut find: impl FnMut(Option<&str>, Option<&str>) -> Result<Vec<ResolvedPlayer>, E>,
+) -> Result<Result<Vec<ResolvedPlayer>, SubmissionError>, E> {
+    if players.is_empty() {
+        return Ok(Err(SubmissionError::NoPlayers));
+    }
+    let max = max_players(puzzle_teams);
+    if players.len() > max {
+        return Ok(Err(SubmissionError::TooManyPlayers(max)));
+    }
+
+    let mut resolved: Vec<ResolvedPlayer> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for player in players {
+        let (id, name) = match player.as_str() {
+            Some(name) => (None, Some(name)),
+            None => (
+                player.get("id").and_then(|id| id.as_str()),
+                player.get("name").and_then(|n| n.as_str()),
+            ),
+        };
+        let label = name.or(id).unwrap_or("").to_string();
+        if id.is_none() && name.is_none() {
+            return Ok(Err(SubmissionError::InvalidPlayer));
+        }
+
+        let mut candidates = find(id, name)?;
+        let candidate = match candidates.len() {
+            0 => return Ok(Err(SubmissionError::UnknownPlayer(label))),
+            1 => candidates.remove(0),
+            _ => {
+                return Ok(Err(SubmissionError::AmbiguousPlayer {
+                    name: label,
+                    ids: candidates.into_iter().map(|c| c.id).collect(),
+                }))
+            }
+        };
+
+        if !seen.insert(candidate.id.clone()) {
+            return Ok(Err(SubmissionError::DuplicatePlayer(candidate.name)));
+        }
+        resolved.push(candidate);
+    }
+
+    let missing: Vec<String> = puzzle_teams.iter()
+        .filter(|team| {
+            let code = teams::by_name(team).map(|team| team.code);
+            !resolved.iter().any(|p| code.is_some_and(|code| p.teams.iter().any(|t| t == code)))
+        })
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        return Ok(Err(SubmissionError::MissingTeams(missing)));
+    }
+
+    Ok(Ok(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    fn puzzle() -> Vec<String> {
+        ["Boston Bruins", "Toronto Maple Leafs", "Montreal Canadiens"].iter().map(|team| team.to_string()).collect()
+    }
+
+    // A tiny dataset: Jagr appears twice by name so names can be ambiguous
+    fn find(id: Option<&str>, name: Option<&str>) -> Result<Vec<ResolvedPlayer>, Infallible> {
+        let players = [
+            ("1", "Jaromir Jagr", vec!["BOS"]),
+            ("2", "Jaromir Jagr", vec!["TOR"]),
+            ("3", "Phil Kessel", vec!["BOS", "TOR"]),
+            ("4", "Carey Price", vec!["MTL"]),
+        ];
+        Ok(players.iter()
+            .filter(|(player_id, player_name, _)| match id {
+                Some(id) => id == *player_id,
+                None => name == Some(*player_name),
+            })
+            .map(|(id, name, teams)| ResolvedPlayer {
+                id: id.to_string(),
+                name: name.to_string(),
+                teams: teams.iter().map(|team| team.to_string()).collect(),
+            })
+            .collect())
+    }
+
+    fn check(players: Value) -> Result<Vec<String>, SubmissionError> {
+        validate_players(players.as_array().unwrap(), &puzzle(), find)
+            .unwrap()
+            .map(|resolved| resolved.into_iter().map(|player| player.id).collect())
+    }
+
+    #[test]
+    fn accepts_a_cover_by_name_or_id() {
+        assert_eq!(check(serde_json::json!(["Phil Kessel", {"name": "Carey Price"}])), Ok(vec!["3".to_string(), "4".to_string()]));
+        assert_eq!(check(serde_json::json!([{"id": "1"}, {"id": "2"}, "Carey Price"])), Ok(vec!["1".to_string(), "2".to_string(), "4".to_string()]));
+    }
+
+    #[test]
+    fn refuses_more_players_than_teams_without_looking_any_up() {
+        let players = serde_json::json!(["a", "b", "c", "d"]);
+        let mut lookups = 0;
+        let result = validate_players(players.as_array().unwrap(), &puzzle(), |_, _| {
+            lookups += 1;
+            Ok::<_, Infallible>(Vec::new())
+        });
+        assert_eq!(result.unwrap().err(), Some(SubmissionError::TooManyPlayers(3)));
+        assert_eq!(lookups, 0);
+    }
+
+    #[test]
+    fn rejects_bad_players() {
+        assert_eq!(check(serde_json::json!([])), Err(SubmissionError::NoPlayers));
+        assert_eq!(check(serde_json::json!([{"team": "BOS"}])), Err(SubmissionError::InvalidPlayer));
+        assert_eq!(check(serde_json::json!(["Wayne Gretzky"])), Err(SubmissionError::UnknownPlayer("Wayne Gretzky".to_string())));
+        assert_eq!(
+            check(serde_json::json!(["Jaromir Jagr"])),
+            Err(SubmissionError::AmbiguousPlayer { name: "Jaromir Jagr".to_string(), ids: vec!["1".to_string(), "2".to_string()] })
+        );
+        assert_eq!(check(serde_json::json!(["Phil Kessel", {"id": "3"}])), Err(SubmissionError::DuplicatePlayer("Phil Kessel".to_string())));
+        assert_eq!(check(serde_json::json!(["Phil Kessel"])), Err(SubmissionError::MissingTeams(vec!["Montreal Canadiens".to_string()])));
+    }
+}
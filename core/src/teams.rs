@@ -0,0 +1,174 @@
+//! The NHL team registry: codes, names, divisions, and the former names and locations that
+//! are counted as today's franchises.
+//!
+//! Current team names are the ones players see in the game. Aliases cover other spellings the
+//! NHL API uses for the same team (e.g. "Montréal Canadiens"). Historical teams carry the seasons
+//! they played so that a reused name, like the original Winnipeg Jets, resolves to the right
+//! franchise for a given season.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conference {
+    Eastern,
+    Western,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Division {
+    Atlantic,
+    Metropolitan,
+    Central,
+    Pacific,
+}
+
+impl Division {
+    pub fn name(self) -> &'static str {
+        match self {
+            Division::Atlantic => "Atlantic",
+            Division::Metropolitan => "Metropolitan",
+            Division::Central => "Central",
+            Division::Pacific => "Pacific",
+        }
+    }
+
+    pub fn conference(self) -> Conference {
+        match self {
+            Division::Atlantic | Division::Metropolitan => Conference::Eastern,
+            Division::Central | Division::Pacific => Conference::Western,
+        }
+    }
+}
+
+/// A franchise as it exists today.
+#[derive(Debug)]
+pub struct Team {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Other names the NHL API uses for this team under its current identity
+    pub aliases: &'static [&'static str],
+    pub division: Division,
+    /// First season (by starting year) played under the current name and location
+    pub active_from: u32,
+}
+
+/// A former name or location, counted as the current franchise it became.
+#[derive(Debug)]
+pub struct HistoricalTeam {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Code of the current team this one is counted as
+    pub becomes: &'static str,
+    /// First and last seasons (by starting year) played under this identity
+    pub active: (u32, u32),
+}
+
+impl Team {
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+impl HistoricalTeam {
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
+    pub fn active_in(&self, season_start: u32) -> bool {
+        season_start >= self.active.0 && season_start <= self.active.1
+    }
+}
+
+macro_rules! team {
+    ($code:literal, $name:literal, [$($alias:literal),*], $division:ident, $from:literal) => {
+        Team { code: $code, name: $name, aliases: &[$($alias),*], division: Division::$division, active_from: $from }
+    };
+}
+
+pub const TEAMS: [Team; 32] = [
+    team!("ANA", "Anaheim Ducks", [], Pacific, 2006),
+    team!("BOS", "Boston Bruins", [], Atlantic, 1924),
+    team!("BUF", "Buffalo Sabres", [], Atlantic, 1970),
+    team!("CGY", "Calgary Flames", [], Pacific, 1980),
+    team!("CAR", "Carolina Hurricanes", [], Metropolitan, 1997),
+    team!("CHI", "Chicago Blackhawks", ["Chicago Black Hawks"], Central, 1926),
+    team!("COL", "Colorado Avalanche", [], Central, 1995),
+    team!("CBJ", "Columbus Blue Jackets", [], Metropolitan, 2000),
+    team!("DAL", "Dallas Stars", [], Central, 1993),
+    team!("DET", "Detroit Red Wings", [], Atlantic, 1932),
+    team!("EDM", "Edmonton Oilers", [], Pacific, 1979),
+    team!("FLA", "Florida Panthers", [], Atlantic, 1993),
+    team!("LAK", "Los Angeles Kings", [], Pacific, 1967),
+    team!("MIN", "Minnesota Wild", [], Central, 2000),
+    team!("MTL", "Montreal Canadiens", ["Montréal Canadiens"], Atlantic, 1917),
+    team!("NSH", "Nashville Predators", [], Central, 1998),
+    team!("NJD", "New Jersey Devils", [], Metropolitan, 1982),
+    team!("NYI", "New York Islanders", [], Metropolitan, 1972),
+    team!("NYR", "New York Rangers", [], Metropolitan, 1926),
+    team!("OTT", "Ottawa Senators", [], Atlantic, 1992),
+    team!("PHI", "Philadelphia Flyers", [], Metropolitan, 1967),
+    team!("PIT", "Pittsburgh Penguins", [], Metropolitan, 1967),
+    team!("SJS", "San Jose Sharks", [], Pacific, 1991),
+    team!("SEA", "Seattle Kraken", [], Pacific, 2021),
+    team!("STL", "St. Louis Blues", [], Central, 1967),
+    team!("TBL", "Tampa Bay Lightning", [], Atlantic, 1992),
+    team!("TOR", "Toronto Maple Leafs", [], Atlantic, 1927),
+    team!("UTA", "Utah Hockey Club", ["Utah Mammoth"], Central, 2024),
+    team!("VAN", "Vancouver Canucks", [], Pacific, 1970),
+    team!("VGK", "Vegas Golden Knights", [], Pacific, 2017),
+    team!("WSH", "Washington Capitals", [], Metropolitan, 1974),
+    team!("WPG", "Winnipeg Jets", [], Central, 2011),
+];
+
+pub const HISTORICAL_TEAMS: [HistoricalTeam; 11] = [
+    HistoricalTeam { code: "ATL", name: "Atlanta Thrashers", aliases: &[], becomes: "WPG", active: (1999, 2010) },
+    HistoricalTeam { code: "HFD", name: "Hartford Whalers", aliases: &[], becomes: "CAR", active: (1979, 1996) },
+    HistoricalTeam { code: "QUE", name: "Quebec Nordiques", aliases: &["Québec Nordiques"], becomes: "COL", active: (1979, 1994) },
+    HistoricalTeam { code: "MNS", name: "Minnesota North Stars", aliases: &[], becomes: "DAL", active: (1967, 1992) },
+    HistoricalTeam { code: "CLR", name: "Colorado Rockies", aliases: &[], becomes: "NJD", active: (1976, 1981) },
+    HistoricalTeam { code: "KCS", name: "Kansas City Scouts", aliases: &[], becomes: "NJD", active: (1974, 1975) },
+    HistoricalTeam { code: "ATF", name: "Atlanta Flames", aliases: &[], becomes: "CGY", active: (1972, 1979) },
+    // Same name as today's Jets, told apart by season
+    HistoricalTeam { code: "WPG1", name: "Winnipeg Jets", aliases: &["Winnipeg Jets (1979)"], becomes: "UTA", active: (1979, 1995) },
+    HistoricalTeam { code: "PHX", name: "Phoenix Coyotes", aliases: &[], becomes: "UTA", active: (1996, 2013) },
+    HistoricalTeam { code: "ARI", name: "Arizona Coyotes", aliases: &[], becomes: "UTA", active: (2014, 2023) },
+    HistoricalTeam { code: "MIG", name: "Mighty Ducks of Anaheim", aliases: &["Anaheim Mighty Ducks"], becomes: "ANA", active: (1993, 2005) },
+];
+
+/// A current team by code, ignoring case.
+pub fn by_code(code: &str) -> Option<&'static Team> {
+    TEAMS.iter().find(|team| team.code.eq_ignore_ascii_case(code))
+}
+
+/// A current team by name or alias, ignoring case.
+pub fn by_name(name: &str) -> Option<&'static Team> {
+    TEAMS.iter().find(|team| team.matches(name))
+}
+
+/// A current team by code, name or alias.
+pub fn lookup(code_or_name: &str) -> Option<&'static Team> {
+    by_code(code_or_name).or_else(|| by_name(code_or_name))
+}
+
+/// The current team code a current or historical code is counted as.
+pub fn current_code(code: &str) -> Option<&'static str> {
+    by_code(code).map(|team| team.code).or_else(|| {
+        HISTORICAL_TEAMS.iter()
+            .find(|team| team.code.eq_ignore_ascii_case(code))
+            .map(|team| team.becomes)
+    })
+}
+
+/// The current team code for a team name as it appeared in a given season, following
+/// relocations and renames.
+pub fn current_code_for_season(name: &str, season_start: u32) -> Option<&'static str> {
+    HISTORICAL_TEAMS.iter()
+        .find(|team| team.active_in(season_start) && team.matches(name))
+        .map(|team| team.becomes)
+        .or_else(|| by_name(name).map(|team| team.code))
+        .or_else(|| HISTORICAL_TEAMS.iter().find(|team| team.matches(name)).map(|team| team.becomes))
+}
+
+/// Current teams in a division.
+pub fn in_division(division: Division) -> impl Iterator<Item = &'static Team> {
+    TEAMS.iter().filter(move |team| team.division == division)
+}
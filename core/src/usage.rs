@@ -0,0 +1,115 @@
+//! Per-day player pick counts, stored as one `daily_usage_{date}` map of player id to picks.
+//!
+//! Every update is a generation-checked write (see `versioned`), so two submissions landing at
+//! once can't overwrite each other's increments.
+
+use crate::versioned::{self, UpdateError, VersionedStore};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+// Give up after this many lost races; a submission should never see anywhere near this
+const MAX_ATTEMPTS: u32 = 16;
+
+pub fn usage_key(date: &str) -> String {
+    format!("daily_usage_{}", date)
+}
+
+/// Current pick counts for a day.
+pub fn get_usage<S: VersionedStore + ?Sized>(store: &S, date: &str) -> Result<HashMap<String, u32>, S::Error> {
+    Ok(match store.read(&usage_key(date))? {
+        Some((body, _)) => serde_json::from_str(&body).unwrap_or_default(),
+        None => HashMap::new(),
+    })
+}
+
+/// Add one pick for each player id and return the counts as written.
+pub fn record_usage<S: VersionedStore + ?Sized>(
+    store: &S,
+    date: &str,
+    player_ids: &[String],
+) -> Result<HashMap<String, u32>, UpdateError<S::Error>> {
+    let recorded = versioned::update(store, &usage_key(date), MAX_ATTEMPTS, |current| {
+        let mut usage_stats: HashMap<String, u32> = current
+            .and_then(|body| serde_json::from_str(body).ok())
+            .unwrap_or_default();
+        for id in player_ids {
+            *usage_stats.entry(id.clone()).or_insert(0) += 1;
+        }
+        let body = serde_json::to_string(&usage_stats).expect("pick counts always serialize");
+        Ok::<_, Infallible>((body, usage_stats))
+    })?;
+    Ok(recorded.unwrap_or_else(|never| match never {}))
+}
+
+/// Share of the day's submissions that picked each player, as a percentage.
+pub fn pick_percentages(usage: &HashMap<String, u32>, total_submissions: usize) -> HashMap<String, f64> {
+    usage.iter()
+        .map(|(player, picks)| {
+            let percentage = if total_submissions > 0 {
+                (*picks as f64 / total_submissions as f64 * 100.0).min(100.0)
+            } else {
+                0.0
+            };
+            (player.clone(), percentage)
+        })
+        .collect()
+}
+
+/// Immaculate Grid style rarity: the sum of each pick's percentage, so lower means rarer picks.
+pub fn rarity_score(player_ids: &[String], percentages: &HashMap<String, f64>) -> f64 {
+    player_ids.iter()
+        .map(|id| percentages.get(id).copied().unwrap_or(0.0))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versioned::testing::MemoryStore;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn stale_writer_retries_instead_of_overwriting() {
+        let store = MemoryStore::default();
+        record_usage(&store, "20000", &["8448208".to_string()]).unwrap();
+
+        // A second writer read generation 1, but a third writer bumps it before the write lands
+        let (body, generation) = store.read(&usage_key("20000")).unwrap().unwrap();
+        record_usage(&store, "20000", &["8448208".to_string()]).unwrap();
+        assert!(!store.write_if(&usage_key("20000"), &body, Some(generation)).unwrap());
+
+        let usage = get_usage(&store, "20000").unwrap();
+        assert_eq!(usage["8448208"], 2);
+    }
+
+    #[test]
+    fn interleaved_submitters_keep_exact_counts() {
+        const SUBMITTERS: usize = 8;
+        const SUBMISSIONS_EACH: usize = 25;
+
+        let store = Arc::new(MemoryStore::default());
+        let barrier = Arc::new(Barrier::new(SUBMITTERS));
+
+        let handles: Vec<_> = (0..SUBMITTERS)
+            .map(|submitter| {
+                let store = Arc::clone(&store);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..SUBMISSIONS_EACH {
+                        let players = vec!["8459400".to_string(), format!("847000{}", submitter % 2)];
+                        record_usage(store.as_ref(), "20000", &players).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let usage = get_usage(store.as_ref(), "20000").unwrap();
+        assert_eq!(usage["8459400"] as usize, SUBMITTERS * SUBMISSIONS_EACH);
+        assert_eq!(usage["8470000"] as usize, SUBMITTERS / 2 * SUBMISSIONS_EACH);
+        assert_eq!(usage["8470001"] as usize, SUBMITTERS / 2 * SUBMISSIONS_EACH);
+    }
+}
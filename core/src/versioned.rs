@@ -0,0 +1,116 @@
+//! Conditional updates to a key-value store with per-key generations, like Fastly's KV store.
+//!
+//! An update reads a value and its generation, changes it, and writes it back only if the
+//! generation hasn't moved. A writer that loses the race re-reads and tries again, so
+//! concurrent updates to the same key never overwrite each other.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// The small piece of a KV store API that conditional updates need.
+pub trait VersionedStore {
+    type Error;
+
+    /// The value and generation stored under `key`, or None if it doesn't exist.
+    fn read(&self, key: &str) -> Result<Option<(String, u64)>, Self::Error>;
+
+    /// Write `value` only if `key` is still at `generation` (or still absent when `generation`
+    /// is None). Returns false when another writer got there first.
+    fn write_if(&self, key: &str, value: &str, generation: Option<u64>) -> Result<bool, Self::Error>;
+}
+
+#[derive(Debug)]
+pub enum UpdateError<E> {
+    /// The store failed
+    Store(E),
+    /// Every attempt lost a race to another writer
+    Contended { key: String, attempts: u32 },
+}
+
+impl<E: fmt::Display> fmt::Display for UpdateError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UpdateError::Store(e) => e.fmt(f),
+            UpdateError::Contended { key, attempts } => {
+                write!(f, "gave up updating {} after {} conflicting writes", key, attempts)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for UpdateError<E> {}
+
+/// Apply `change` to the value under `key` (None if it doesn't exist yet) and write back the
+/// value it returns, starting over if someone else wrote first.
+///
+/// `change` can refuse by returning an error, which is passed back without writing anything.
+/// Otherwise the result is whatever `change` returned alongside the value that was written.
+pub fn update<S, T, R>(
+    store: &S,
+    key: &str,
+    max_attempts: u32,
+    mut change: impl FnMut(Option<&str>) -> Result<(String, T), R>,
+) -> Result<Result<T, R>, UpdateError<S::Error>>
+where
+    S: VersionedStore + ?Sized,
+{
+    for attempt in 0..max_attempts {
+        let current = store.read(key).map_err(UpdateError::Store)?;
+        let (value, result) = match change(current.as_ref().map(|(body, _)| body.as_str())) {
+            Ok(changed) => changed,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let generation = current.map(|(_, generation)| generation);
+        if store.write_if(key, &value, generation).map_err(UpdateError::Store)? {
+            return Ok(Ok(result));
+        }
+
+        // Someone else wrote first; back off with jitter so the losers don't collide again
+        let base = 1u64 << attempt.min(6);
+        std::thread::sleep(Duration::from_millis(base + jitter() % base));
+    }
+
+    Err(UpdateError::Contended { key: key.to_string(), attempts: max_attempts })
+}
+
+// Randomly seeded hashers are the standard library's only source of randomness
+fn jitter() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::VersionedStore;
+    use std::collections::HashMap;
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+
+    /// Mimics KV generations; yields between calls so concurrent writers interleave.
+    #[derive(Default)]
+    pub struct MemoryStore {
+        entries: Mutex<HashMap<String, (String, u64)>>,
+    }
+
+    impl VersionedStore for MemoryStore {
+        type Error = Infallible;
+
+        fn read(&self, key: &str) -> Result<Option<(String, u64)>, Infallible> {
+            let value = self.entries.lock().unwrap().get(key).cloned();
+            std::thread::yield_now();
+            Ok(value)
+        }
+
+        fn write_if(&self, key: &str, value: &str, generation: Option<u64>) -> Result<bool, Infallible> {
+            let mut entries = self.entries.lock().unwrap();
+            let current = entries.get(key).map(|(_, generation)| *generation);
+            if current != generation {
+                return Ok(false);
+            }
+            entries.insert(key.to_string(), (value.to_string(), current.unwrap_or(0) + 1));
+            Ok(true)
+        }
+    }
+}